@@ -0,0 +1,224 @@
+use crate::boss::Boss;
+use crate::bullets::Bullet;
+use crate::collision::Cell;
+use crate::player::{Action, Player};
+use crate::powerups::Powerup;
+use crate::world::{self, PLAYER_ROW};
+use crate::Game;
+
+// Agents fly a ship in place of a player. Every tick an agent is shown what the
+// player could see on screen and picks what to do, the same moves a player has on
+// the keyboard, so an agent can't do anything a player couldn't. The autopilot is
+// the one built in, for demos and for playing many games to check the balance
+
+pub trait Agent {
+    // What to do this tick, in order. Moves are one column each
+    fn act(&mut self, view: &GameView) -> Vec<Action>;
+}
+
+// What an agent can see of the game. It borrows the game, so an agent can look but
+// never change anything
+pub struct GameView<'a> {
+    pub me: usize, // Which of the players the agent is flying
+    pub players: &'a [Player],
+    pub enemies: Vec<Cell>,
+    pub bullets: &'a [Bullet],
+    pub powerups: &'a [(i32, i32, Powerup)],
+    pub boss: Option<&'a Boss>,
+    pub bunkers: Vec<Cell>,
+}
+
+impl<'a> GameView<'a> {
+    pub fn of(game: &'a Game, me: usize) -> Self {
+        GameView {
+            me,
+            players: &game.players,
+            enemies: game
+                .enemies
+                .iter()
+                .map(|enemy| (enemy.x, enemy.y))
+                .collect(),
+            bullets: &game.bullets,
+            powerups: &game.powerups,
+            boss: game.boss.as_ref(),
+            bunkers: game.bunkers.cells().map(|(x, y, _)| (x, y)).collect(),
+        }
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.players[self.me]
+    }
+}
+
+// The built-in agents by name, for picking one on the command line
+pub const NAMES: [&str; 2] = ["autopilot", "idle"];
+
+pub fn by_name(name: &str) -> Option<Box<dyn Agent>> {
+    match name {
+        "autopilot" => Some(Box::new(Autopilot)),
+        "idle" => Some(Box::new(Idle)),
+        _ => None,
+    }
+}
+
+// Let an agent take its turn as player `me`
+pub fn steer(game: &mut Game, me: usize, agent: &mut dyn Agent) {
+    if game.paused || !game.players.get(me).is_some_and(Player::in_game) {
+        return;
+    }
+    let actions = agent.act(&GameView::of(game, me));
+    for action in actions {
+        game.handle_action(me, action);
+    }
+}
+
+// Never moves or fires, how long a wave takes to win on its own is a baseline for
+// other agents
+pub struct Idle;
+
+impl Agent for Idle {
+    fn act(&mut self, _view: &GameView) -> Vec<Action> {
+        Vec::new()
+    }
+}
+
+// Ticks ahead the autopilot looks for bullets coming down on it
+const LOOKAHEAD: u32 = 8;
+// How far a falling powerup can be for the autopilot to go and get it
+const POWERUP_REACH: i32 = 8;
+
+// A simple bot that stays out from under falling bullets and otherwise lines up
+// with the lowest enemy, the one closest to invading, and shoots it. It won't waste
+// shots on its own bunkers
+#[derive(Default)]
+pub struct Autopilot;
+
+impl Agent for Autopilot {
+    fn act(&mut self, view: &GameView) -> Vec<Action> {
+        let x = view.player().x;
+        let target = pick_target(view, x);
+
+        // Of staying put and one step either way, take the safest, then whichever
+        // gets closer to the target
+        let moves = [
+            (None, x),
+            (Some(Action::Left), x - 1),
+            (Some(Action::Right), x + 1),
+        ];
+        let (step, to) = moves
+            .into_iter()
+            .filter(|&(_, to)| world::on_screen(to, PLAYER_ROW))
+            .min_by_key(|&(_, to)| {
+                (
+                    danger(view, to),
+                    target.map_or(0, |target| (target.x - to).abs()),
+                )
+            })
+            .unwrap_or((None, x));
+
+        let mut actions: Vec<Action> = step.into_iter().collect();
+        let fire = target.is_some_and(|target| {
+            target.shoot
+                && (target.x - to).abs() <= 1
+                && (target.through_bunkers || !sheltered(view, to, 0))
+        });
+        if fire {
+            actions.push(Action::Fire);
+        }
+        actions
+    }
+}
+
+// Where the autopilot is heading
+#[derive(Clone, Copy)]
+struct Target {
+    x: i32,
+    shoot: bool,
+    through_bunkers: bool, // Only worth it when there is nothing in the open
+}
+
+impl Target {
+    fn new(x: i32, shoot: bool) -> Self {
+        Target {
+            x,
+            shoot,
+            through_bunkers: false,
+        }
+    }
+}
+
+// A powerup falling nearby comes first, then the lowest enemy in the open, then the
+// lowest behind a bunker, then the boss
+fn pick_target(view: &GameView, x: i32) -> Option<Target> {
+    let powerup = view
+        .powerups
+        .iter()
+        .filter(|&&(px, py, _)| {
+            let rows_left = PLAYER_ROW - py;
+            rows_left >= 0 && (px - x).abs() <= rows_left.min(POWERUP_REACH)
+        })
+        .min_by_key(|&&(px, _, _)| (px - x).abs());
+    if let Some(&(px, _, _)) = powerup {
+        return Some(Target::new(px, false));
+    }
+    let lowest = view
+        .enemies
+        .iter()
+        .max_by_key(|&&(ex, ey)| (!sheltered(view, ex, ey), ey, -(ex - x).abs()));
+    if let Some(&(ex, ey)) = lowest {
+        return Some(Target {
+            through_bunkers: sheltered(view, ex, ey),
+            ..Target::new(ex, true)
+        });
+    }
+    view.boss.and_then(|boss| {
+        boss.parts
+            .iter()
+            .filter(|part| !part.is_destroyed())
+            .map(|part| boss.part_center(part).0)
+            .min_by_key(|&bx| (bx - x).abs())
+            .map(|bx| Target::new(bx, true))
+    })
+}
+
+// Whether a bunker stands in column `x` below row `y`, between there and the ship
+fn sheltered(view: &GameView, x: i32, y: i32) -> bool {
+    view.bunkers.iter().any(|&(bx, by)| bx == x && by > y)
+}
+
+// How much trouble the ship would be in at column `x`. Each enemy bullet is played
+// forward a few ticks, and counts more the sooner it would sweep through the ship or
+// right beside it. Enemies about to ram the ship count too
+fn danger(view: &GameView, x: i32) -> u32 {
+    let mut danger = 0;
+    for bullet in view.bullets.iter().filter(|b| b.is_enemy) {
+        let mut bullet = bullet.clone();
+        for ticks in 0..LOOKAHEAD {
+            bullet.step(1.0);
+            let path = bullet.path();
+            // Bunkers stop bullets, the ship is safe behind one
+            if path.iter().any(|cell| view.bunkers.contains(cell)) {
+                break;
+            }
+            let closest = path
+                .iter()
+                .filter(|&&(_, y)| y == PLAYER_ROW)
+                .map(|&(px, _)| (px - x).abs())
+                .min();
+            match closest {
+                Some(0) => danger += 2 * (LOOKAHEAD - ticks),
+                Some(1) => danger += LOOKAHEAD - ticks,
+                _ => {}
+            }
+            if bullet.y > PLAYER_ROW as f32 + 1.0 {
+                break;
+            }
+        }
+    }
+    for &(ex, ey) in &view.enemies {
+        if PLAYER_ROW - ey <= 2 && (ex - x).abs() <= 1 {
+            danger += 2 * LOOKAHEAD;
+        }
+    }
+    danger
+}
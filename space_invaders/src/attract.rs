@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+use termion::color;
+use termion::event::Key;
+
+use crate::agent::{self, Autopilot};
+use crate::display_start_screen::display_start_screen;
+use crate::game_mode::GameMode;
+use crate::leaderboard::{Category, Leaderboard, ENTRIES};
+use crate::levels::Wave;
+use crate::patterns::Patterns;
+use crate::{Game, HEIGHT, TICK_DURATION};
+
+// The attract loop an arcade cabinet runs while nobody is playing: once the title
+// screen has sat idle for a while it cycles through a demo game flown by the
+// autopilot, the high scores and the title again, until a key is pressed
+
+// How long the title screen waits for a key before the attract loop starts
+pub const IDLE_DELAY: Duration = Duration::from_secs(15);
+// The longest a demo game runs, it ends sooner if the autopilot loses
+const DEMO_LENGTH: Duration = Duration::from_secs(40);
+const HIGH_SCORES_LENGTH: Duration = Duration::from_secs(8);
+const TITLE_LENGTH: Duration = Duration::from_secs(10);
+
+// Cycle through the attract screens until a key is pressed. The key only ends the
+// loop, it isn't passed on to the menu
+pub fn run(
+    screen: &mut impl Write,
+    rx: &mpsc::Receiver<Key>,
+    waves: &[Wave],
+    patterns: &Patterns,
+    mode: GameMode,
+) -> io::Result<()> {
+    loop {
+        if demo(screen, rx, waves, patterns, mode)? {
+            return Ok(());
+        }
+        display_high_scores(screen, &Leaderboard::load())?;
+        if wait(rx, HIGH_SCORES_LENGTH) {
+            return Ok(());
+        }
+        display_start_screen(screen)?;
+        if wait(rx, TITLE_LENGTH) {
+            return Ok(());
+        }
+    }
+}
+
+// Play a silent game with the autopilot at the controls. Returns whether a key was
+// pressed. The score is never saved
+fn demo(
+    screen: &mut impl Write,
+    rx: &mpsc::Receiver<Key>,
+    waves: &[Wave],
+    patterns: &Patterns,
+    mode: GameMode,
+) -> io::Result<bool> {
+    let mut game = Game::new();
+    game.waves = waves.to_vec();
+    game.patterns = patterns.clone();
+    game.mode = mode;
+    game.start_level();
+    let mut pilot = Autopilot;
+    let started = Instant::now();
+    let mut last_update = Instant::now();
+
+    while !game.is_game_over() && started.elapsed() < DEMO_LENGTH {
+        if last_update.elapsed() >= TICK_DURATION {
+            agent::steer(&mut game, 0, &mut pilot);
+            game.update();
+            game.events.clear(); // A demo makes no sound
+            write!(screen, "{}{}", termion::clear::All, game.render())?;
+            write!(
+                screen,
+                "{}{}{}DEMO - press any key{}{}",
+                termion::cursor::Goto(10, HEIGHT as u16 + 3),
+                termion::style::Bold,
+                color::Fg(color::LightRed),
+                color::Fg(color::Reset),
+                termion::style::Reset
+            )?;
+            screen.flush()?;
+            last_update = Instant::now();
+        }
+        if !matches!(rx.try_recv(), Err(TryRecvError::Empty)) {
+            return Ok(true);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    Ok(false)
+}
+
+fn display_high_scores(screen: &mut impl Write, leaderboard: &Leaderboard) -> io::Result<()> {
+    write!(screen, "{}", termion::clear::All)?;
+    write!(
+        screen,
+        "{}{}{}H I G H   S C O R E S{}",
+        termion::cursor::Goto(8, 6),
+        termion::style::Bold,
+        color::Fg(color::LightCyan),
+        color::Fg(color::Reset)
+    )?;
+    for (column, category) in [(6, Category::Solo), (22, Category::Coop)] {
+        write!(
+            screen,
+            "{}{}{}",
+            termion::cursor::Goto(column, 9),
+            color::Fg(color::Yellow),
+            category.name().to_uppercase()
+        )?;
+        let top = leaderboard.top(category);
+        for place in 0..ENTRIES {
+            let score = top.get(place).map_or("---".to_string(), u32::to_string);
+            write!(
+                screen,
+                "{}{}{}. {:>6}",
+                termion::cursor::Goto(column, 11 + place as u16),
+                color::Fg(color::LightYellow),
+                place + 1,
+                score
+            )?;
+        }
+    }
+    write!(
+        screen,
+        "{}{}Press any key{}",
+        termion::cursor::Goto(11, 19),
+        color::Fg(color::Green),
+        color::Fg(color::Reset)
+    )?;
+    screen.flush()
+}
+
+// Wait up to `duration` for a key. Returns whether one was pressed, or input is gone
+fn wait(rx: &mpsc::Receiver<Key>, duration: Duration) -> bool {
+    !matches!(rx.recv_timeout(duration), Err(RecvTimeoutError::Timeout))
+}
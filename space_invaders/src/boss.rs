@@ -0,0 +1,441 @@
+use std::time::Duration;
+use termion::color;
+
+use crate::bosses::BossKind;
+use crate::collision::Cell;
+use crate::{ticks, world};
+
+// How long the intro banner shows. The boss holds its fire and stays put meanwhile
+const INTRO_TICKS: u64 = ticks(Duration::from_secs(3));
+
+// A boss is a hull sprite with destructible parts laid over it. The hull blocks
+// bullets, the parts can be shot off one by one, and destroying every core destroys
+// the boss. How it looks, moves and fights is up to its BossKind, see bosses.rs
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartKind {
+    Turret,
+    Shield,
+    Core,
+    Hangar,
+    Segment,
+}
+
+impl PartKind {
+    fn color(self) -> &'static dyn color::Color {
+        match self {
+            PartKind::Turret => &color::Red,
+            PartKind::Shield => &color::LightCyan,
+            PartKind::Core => &color::Yellow,
+            PartKind::Hangar => &color::LightGreen,
+            PartKind::Segment => &color::Green,
+        }
+    }
+
+    // Points for shooting the part off, the core is worth the whole boss
+    pub fn score_value(self) -> u32 {
+        match self {
+            PartKind::Turret => 100,
+            PartKind::Shield => 200,
+            PartKind::Core => 1000,
+            PartKind::Hangar => 150,
+            PartKind::Segment => 50,
+        }
+    }
+}
+
+// A destructible piece of the boss, drawn as `glyphs` rightwards from its offset
+pub struct Part {
+    pub kind: PartKind,
+    pub dx: i32, // Offset from the boss's top-left corner
+    pub dy: i32,
+    pub glyphs: &'static str,
+    pub health: u16,
+    pub max_health: u16,
+}
+
+impl Part {
+    pub fn new(kind: PartKind, dx: i32, dy: i32, glyphs: &'static str, max_health: u16) -> Self {
+        Part {
+            kind,
+            dx,
+            dy,
+            glyphs,
+            health: max_health,
+            max_health,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.glyphs.chars().count() as i32
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.health == 0
+    }
+}
+
+// What a bullet struck on its way through the boss
+pub enum BossHit {
+    Hull,
+    Part {
+        kind: PartKind,
+        destroyed: bool,
+        center: (i32, i32),
+    },
+}
+
+// Positions are in world coordinates, (x, y) is the sprite's top-left corner
+pub struct Boss {
+    pub kind: &'static dyn BossKind,
+    pub x: i32,
+    pub y: i32,
+    pub parts: Vec<Part>,
+    pub shoot_timer: u8,
+    pub move_timer: u32,
+    pub direction: i8,
+    pub vertical_direction: i8,
+    pub volleys: u32, // Volleys fired so far
+    pub intro_ticks: u64,
+}
+
+impl Boss {
+    // Bosses on later levels have tougher parts
+    pub fn new(kind: &'static dyn BossKind, level: usize) -> Self {
+        let (x, y) = kind.start();
+        Boss {
+            kind,
+            x,
+            y,
+            parts: kind.parts((level / 5) as u16),
+            shoot_timer: 0,
+            move_timer: 0,
+            direction: 1,
+            vertical_direction: 1,
+            volleys: 0,
+            intro_ticks: INTRO_TICKS,
+        }
+    }
+
+    pub fn height(&self) -> i32 {
+        self.kind.sprite().len() as i32
+    }
+
+    pub fn width(&self) -> i32 {
+        self.kind
+            .sprite()
+            .iter()
+            .map(|row| row.chars().count() as i32)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Parts of a kind that are still standing
+    pub fn standing(&self, kind: PartKind) -> impl Iterator<Item = &Part> {
+        self.parts
+            .iter()
+            .filter(move |part| part.kind == kind && !part.is_destroyed())
+    }
+
+    pub fn has(&self, kind: PartKind) -> bool {
+        self.standing(kind).next().is_some()
+    }
+
+    // From 1 to 3, the boss gets angrier as it loses parts
+    pub fn phase(&self) -> u8 {
+        self.kind.phase(self)
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        !self.has(PartKind::Core)
+    }
+
+    // Remaining health of all parts, from 0.0 to 1.0, for the health bar
+    pub fn health_fraction(&self) -> f32 {
+        let health: u32 = self.parts.iter().map(|part| part.health as u32).sum();
+        let max_health: u32 = self.parts.iter().map(|part| part.max_health as u32).sum();
+        health as f32 / max_health as f32
+    }
+
+    // Advance the boss's timers by one tick, slowed down by `slow`. Moves the boss
+    // when it's time to, and returns whether it fires a volley this tick
+    pub fn tick(&mut self, player_x: i32, slow: usize) -> bool {
+        if self.intro_ticks > 0 {
+            self.intro_ticks -= 1;
+            return false;
+        }
+        let phase = self.phase();
+        self.move_timer += 1;
+        if self.move_timer >= self.kind.move_interval(phase) * slow as u32 {
+            self.move_timer = 0;
+            self.kind.step(self, player_x);
+        }
+        self.shoot_timer += 1;
+        if self.shoot_timer >= self.kind.fire_interval(phase) * slow as u8 {
+            self.shoot_timer = 0;
+            self.volleys += 1;
+            return true;
+        }
+        false
+    }
+
+    // Bullet patterns fired in one volley, and where from
+    pub fn fire(&self) -> Vec<(&'static str, Cell)> {
+        self.kind.fire(self)
+    }
+
+    // Where the enemies launched with a volley appear
+    pub fn launch(&self) -> Vec<(i32, i32)> {
+        self.kind.launch(self)
+    }
+
+    // The part covering a world cell, if any
+    fn part_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.parts.iter().position(|part| {
+            let (left, top) = (self.x + part.dx, self.y + part.dy);
+            y == top && (left..left + part.width()).contains(&x)
+        })
+    }
+
+    // Whether the hull sprite has something other than a gap at a world cell
+    fn hull_at(&self, x: i32, y: i32) -> bool {
+        let (dx, dy) = (x - self.x, y - self.y);
+        usize::try_from(dy)
+            .ok()
+            .and_then(|dy| self.kind.sprite().get(dy))
+            .zip(usize::try_from(dx).ok())
+            .and_then(|(row, dx)| row.chars().nth(dx))
+            .is_some_and(|c| c != ' ')
+    }
+
+    // Follow a bullet's path through the boss. The first living part or piece of
+    // hull it reaches stops it; destroyed parts and gaps in the hull let it through,
+    // and a core the boss keeps covered shrugs the bullet off like hull
+    pub fn hit(&mut self, path: &[Cell]) -> Option<BossHit> {
+        let core_exposed = self.kind.core_exposed(self);
+        for &(x, y) in path {
+            match self.part_at(x, y) {
+                Some(index) if self.parts[index].is_destroyed() => continue,
+                Some(index) if self.parts[index].kind == PartKind::Core && !core_exposed => {
+                    return Some(BossHit::Hull);
+                }
+                Some(index) => {
+                    self.parts[index].health -= 1;
+                    let part = &self.parts[index];
+                    return Some(BossHit::Part {
+                        kind: part.kind,
+                        destroyed: part.is_destroyed(),
+                        center: self.part_center(part),
+                    });
+                }
+                None => {
+                    if self.hull_at(x, y) {
+                        return Some(BossHit::Hull);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // A smart bomb wears down every part but the cores
+    pub fn bomb(&mut self, damage: u16) {
+        for part in &mut self.parts {
+            if part.kind != PartKind::Core {
+                part.health = part.health.saturating_sub(damage);
+            }
+        }
+    }
+
+    // World cell of a part's middle, where its explosion goes
+    pub fn part_center(&self, part: &Part) -> (i32, i32) {
+        (self.x + part.dx + part.width() / 2, self.y + part.dy)
+    }
+
+    // Every visible cell of the boss, with destroyed parts drawn as wreckage
+    pub fn cells(&self) -> Vec<(i32, i32, char, &'static dyn color::Color)> {
+        let mut cells: Vec<(i32, i32, char, &'static dyn color::Color)> = Vec::new();
+        for (dy, row) in self.kind.sprite().iter().enumerate() {
+            for (dx, c) in row.chars().enumerate() {
+                let (x, y) = (self.x + dx as i32, self.y + dy as i32);
+                if c != ' ' && self.part_at(x, y).is_none() {
+                    cells.push((x, y, c, &color::Magenta));
+                }
+            }
+        }
+        for part in &self.parts {
+            for (i, c) in part.glyphs.chars().enumerate() {
+                let (x, y) = (self.x + part.dx + i as i32, self.y + part.dy);
+                if part.is_destroyed() {
+                    cells.push((x, y, '.', &color::LightBlack));
+                } else {
+                    cells.push((x, y, c, part.kind.color()));
+                }
+            }
+        }
+        cells
+    }
+
+    // Whether the whole boss is inside the playfield
+    pub fn on_screen(&self) -> bool {
+        self.cells()
+            .iter()
+            .all(|&(x, y, _, _)| world::on_screen(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bosses::{Mothership, Worm, ROSTER};
+
+    // Shoot every standing part of a kind off
+    fn destroy(boss: &mut Boss, kind: PartKind) {
+        for part in boss.parts.iter_mut().filter(|part| part.kind == kind) {
+            part.health = 0;
+        }
+    }
+
+    // A bullet flying straight up a column, from below the boss to above it. The
+    // worm has no hull, so its height doesn't reach down to its parts
+    fn upwards(boss: &Boss, dx: i32) -> Vec<Cell> {
+        (-1..=10)
+            .rev()
+            .map(|dy| (boss.x + dx, boss.y + dy))
+            .collect()
+    }
+
+    #[test]
+    fn phases_follow_the_parts_shot_off() {
+        let mut boss = Boss::new(&Mothership, 5);
+        assert_eq!(boss.phase(), 1);
+        destroy(&mut boss, PartKind::Hangar);
+        assert_eq!(boss.phase(), 1);
+        destroy(&mut boss, PartKind::Turret);
+        assert_eq!(boss.phase(), 2);
+        destroy(&mut boss, PartKind::Shield);
+        assert_eq!(boss.phase(), 3);
+        assert!(!boss.is_destroyed());
+        destroy(&mut boss, PartKind::Core);
+        assert!(boss.is_destroyed());
+    }
+
+    #[test]
+    fn bullets_wear_parts_down_then_pass_their_wreckage() {
+        let mut boss = Boss::new(&Mothership, 5);
+        let shield = boss.parts.iter().position(|p| p.kind == PartKind::Shield);
+        let shield = shield.unwrap();
+        boss.parts[shield].health = 2;
+
+        let path = upwards(&boss, 5);
+        let hit = boss.hit(&path);
+        assert!(matches!(
+            hit,
+            Some(BossHit::Part {
+                kind: PartKind::Shield,
+                destroyed: false,
+                ..
+            })
+        ));
+        let hit = boss.hit(&path);
+        assert!(matches!(
+            hit,
+            Some(BossHit::Part {
+                kind: PartKind::Shield,
+                destroyed: true,
+                ..
+            })
+        ));
+        // The shield is wreckage now, the next bullet reaches the core behind it
+        let hit = boss.hit(&path);
+        assert!(matches!(
+            hit,
+            Some(BossHit::Part {
+                kind: PartKind::Core,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn hull_stops_bullets_and_gaps_let_them_by() {
+        let mut boss = Boss::new(&Mothership, 5);
+        let health: Vec<u16> = boss.parts.iter().map(|p| p.health).collect();
+        assert!(matches!(boss.hit(&upwards(&boss, 0)), Some(BossHit::Hull)));
+        assert!(boss.hit(&upwards(&boss, -1)).is_none());
+        let after: Vec<u16> = boss.parts.iter().map(|p| p.health).collect();
+        assert_eq!(health, after);
+    }
+
+    #[test]
+    fn covered_cores_shrug_bullets_off() {
+        let mut boss = Boss::new(&Worm, 10);
+        let core = boss.parts.iter().position(|p| p.kind == PartKind::Core);
+        let core = core.unwrap();
+        let (dx, health) = (boss.parts[core].dx, boss.parts[core].health);
+
+        assert!(matches!(boss.hit(&upwards(&boss, dx)), Some(BossHit::Hull)));
+        assert_eq!(boss.parts[core].health, health);
+
+        destroy(&mut boss, PartKind::Segment);
+        assert!(matches!(
+            boss.hit(&upwards(&boss, dx)),
+            Some(BossHit::Part {
+                kind: PartKind::Core,
+                ..
+            })
+        ));
+        assert_eq!(boss.parts[core].health, health - 1);
+    }
+
+    #[test]
+    fn bombs_spare_the_cores() {
+        let mut boss = Boss::new(&Mothership, 5);
+        boss.bomb(3);
+        for part in &boss.parts {
+            let expected = match part.kind {
+                PartKind::Core => part.max_health,
+                _ => part.max_health - 3,
+            };
+            assert_eq!(part.health, expected, "{:?}", part.kind);
+        }
+        boss.bomb(u16::MAX);
+        assert!(!boss.is_destroyed());
+        assert_eq!(boss.phase(), 3);
+    }
+
+    #[test]
+    fn health_fraction_stays_between_nothing_and_full() {
+        for kind in ROSTER {
+            for level in [5, 25, 100, 1000] {
+                let mut boss = Boss::new(kind, level);
+                assert_eq!(boss.health_fraction(), 1.0, "{}", kind.name());
+                boss.bomb(1);
+                let fraction = boss.health_fraction();
+                assert!((0.0..1.0).contains(&fraction), "{}", kind.name());
+                boss.bomb(u16::MAX);
+                destroy(&mut boss, PartKind::Core);
+                assert_eq!(boss.health_fraction(), 0.0, "{}", kind.name());
+            }
+        }
+    }
+
+    #[test]
+    fn the_intro_holds_fire_then_volleys_come_on_time() {
+        let mut boss = Boss::new(&Mothership, 5);
+        let start = (boss.x, boss.y);
+        for _ in 0..INTRO_TICKS {
+            assert!(!boss.tick(30, 1));
+        }
+        assert_eq!((boss.x, boss.y), start);
+
+        let interval = Mothership.fire_interval(1) as usize;
+        let fired: Vec<usize> = (1..=interval * 2).filter(|_| boss.tick(30, 1)).collect();
+        assert_eq!(fired, [interval, interval * 2]);
+        assert_eq!(boss.volleys, 2);
+
+        // Time Slow stretches the wait between volleys
+        let fired = (1..=interval * 2).filter(|_| boss.tick(30, 2)).count();
+        assert_eq!(fired, 1);
+    }
+}
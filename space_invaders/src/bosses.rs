@@ -0,0 +1,509 @@
+use crate::boss::{Boss, Part, PartKind};
+use crate::collision::Cell;
+use crate::WIDTH;
+
+// Everything that makes one boss different from another. Adding a boss means
+// implementing this for a new type and listing it in ROSTER
+pub trait BossKind: Sync {
+    // Shown in the intro banner when the boss arrives
+    fn name(&self) -> &'static str;
+    fn intro(&self) -> &'static str;
+
+    // Hull drawn behind the parts, one string per row. Bullets stop on anything but
+    // a space. Bosses made only of parts have none
+    fn sprite(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    // The boss's destructible parts, tougher on later levels
+    fn parts(&self, toughness: u16) -> Vec<Part>;
+
+    // Where the sprite's top-left corner starts
+    fn start(&self) -> (i32, i32);
+
+    // From 1 to 3: turrets go first, then the shield, then the bare core
+    fn phase(&self, boss: &Boss) -> u8 {
+        if boss.has(PartKind::Turret) {
+            1
+        } else if boss.has(PartKind::Shield) {
+            2
+        } else {
+            3
+        }
+    }
+
+    // Ticks between volleys and between steps in each phase
+    fn fire_interval(&self, phase: u8) -> u8;
+    fn move_interval(&self, phase: u8) -> u32;
+
+    // Move one step, `player_x` is the player's column
+    fn step(&self, boss: &mut Boss, player_x: i32);
+
+    // Bullet patterns fired in one volley, each with the cell it is fired from.
+    // Patterns are looked up by name, see patterns.txt
+    fn fire(&self, boss: &Boss) -> Vec<(&'static str, Cell)>;
+
+    // Where enemies launched with a volley appear
+    fn launch(&self, _boss: &Boss) -> Vec<(i32, i32)> {
+        Vec::new()
+    }
+
+    // Whether bullets can damage the cores yet, covered cores block them like hull
+    fn core_exposed(&self, _boss: &Boss) -> bool {
+        true
+    }
+}
+
+pub struct Mothership;
+pub struct Worm;
+pub struct Mirror;
+pub struct Tank;
+
+// Every boss, in the order they take turns on boss levels
+pub const ROSTER: [&dyn BossKind; 4] = [&Mothership, &Worm, &Mirror, &Tank];
+
+// The boss for a level. Boss levels usually come every fifth level, each one brings
+// the next boss in the roster
+pub fn for_level(level: usize) -> &'static dyn BossKind {
+    ROSTER[(level / 5).saturating_sub(1) % ROSTER.len()]
+}
+
+// Slide sideways, turning around at the edges of the playfield
+fn slide(boss: &mut Boss) {
+    let max_x = WIDTH as i32 - boss.width();
+    if boss.x <= 0 || boss.x >= max_x {
+        boss.direction *= -1;
+    }
+    boss.x = (boss.x + boss.direction as i32).clamp(0, max_x);
+}
+
+// A pattern fired from below every standing part of a kind
+fn fire_from(boss: &Boss, kind: PartKind, pattern: &'static str) -> Vec<(&'static str, Cell)> {
+    boss.standing(kind)
+        .map(|part| (pattern, (boss.x + part.dx, boss.y + boss.height())))
+        .collect()
+}
+
+// Hangars launch a wave of enemies every few volleys
+const LAUNCH_VOLLEYS: u32 = 4;
+
+impl BossKind for Mothership {
+    fn name(&self) -> &'static str {
+        "MOTHERSHIP"
+    }
+    fn intro(&self) -> &'static str {
+        "Its hangars keep launching invaders"
+    }
+    fn sprite(&self) -> &'static [&'static str] {
+        #[rustfmt::skip]
+        const SPRITE: &[&str] = &[
+            " _/=====\\_ ",
+            "/H (@@@) H\\",
+            "\\T=[===]=T/",
+        ];
+        SPRITE
+    }
+    fn parts(&self, toughness: u16) -> Vec<Part> {
+        vec![
+            Part::new(PartKind::Turret, 1, 2, "T", 5 + 2 * toughness),
+            Part::new(PartKind::Turret, 9, 2, "T", 5 + 2 * toughness),
+            Part::new(PartKind::Hangar, 1, 1, "H", 6 + 2 * toughness),
+            Part::new(PartKind::Hangar, 9, 1, "H", 6 + 2 * toughness),
+            Part::new(PartKind::Shield, 3, 2, "[===]", 10 + 4 * toughness),
+            Part::new(PartKind::Core, 4, 1, "@@@", 15 + 5 * toughness),
+        ]
+    }
+    fn start(&self) -> (i32, i32) {
+        ((WIDTH as i32 - 11) / 2, 3)
+    }
+    fn fire_interval(&self, phase: u8) -> u8 {
+        match phase {
+            1 => 20,
+            2 => 16,
+            _ => 12,
+        }
+    }
+    fn move_interval(&self, phase: u8) -> u32 {
+        match phase {
+            1 | 2 => 12,
+            _ => 6,
+        }
+    }
+    fn step(&self, boss: &mut Boss, _player_x: i32) {
+        slide(boss);
+    }
+    fn fire(&self, boss: &Boss) -> Vec<(&'static str, Cell)> {
+        let core = (boss.x + boss.width() / 2, boss.y + boss.height());
+        match boss.phase() {
+            // Every standing turret fires straight down
+            1 => fire_from(boss, PartKind::Turret, "down"),
+            // The core sprays through the shield
+            2 => vec![("spray", core)],
+            // The exposed core adds an aimed shot to its spray
+            _ => vec![("fury", core)],
+        }
+    }
+
+    // `%` rather than is_multiple_of, which needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn launch(&self, boss: &Boss) -> Vec<(i32, i32)> {
+        if boss.volleys % LAUNCH_VOLLEYS != 0 {
+            return Vec::new();
+        }
+        boss.standing(PartKind::Hangar)
+            .map(|part| (boss.x + part.dx, boss.y + boss.height()))
+            .collect()
+    }
+}
+
+// The worm's head bounces between these rows, dropping two rows at each edge
+const WORM_TOP: i32 = 3;
+const WORM_BOTTOM: i32 = 15;
+const WORM_SEGMENTS: i32 = 10;
+
+// The worm has no hull, so it stays at the world's origin and moves its parts
+// instead. Its head is the first part, the segments trail behind it in order
+impl BossKind for Worm {
+    fn name(&self) -> &'static str {
+        "THE WORM"
+    }
+    fn intro(&self) -> &'static str {
+        "Shoot off its segments to expose the head"
+    }
+    fn parts(&self, toughness: u16) -> Vec<Part> {
+        let mut parts = vec![Part::new(
+            PartKind::Core,
+            WORM_SEGMENTS,
+            WORM_TOP,
+            "@",
+            12 + 4 * toughness,
+        )];
+        for i in 0..WORM_SEGMENTS {
+            parts.push(Part::new(
+                PartKind::Segment,
+                WORM_SEGMENTS - 1 - i,
+                WORM_TOP,
+                "O",
+                2 + toughness,
+            ));
+        }
+        parts
+    }
+    fn start(&self) -> (i32, i32) {
+        (0, 0)
+    }
+    // The worm gets faster and angrier as it loses its segments
+    fn phase(&self, boss: &Boss) -> u8 {
+        let standing = boss.standing(PartKind::Segment).count() as i32;
+        if standing * 3 > WORM_SEGMENTS * 2 {
+            1
+        } else if standing * 3 > WORM_SEGMENTS {
+            2
+        } else {
+            3
+        }
+    }
+    fn fire_interval(&self, phase: u8) -> u8 {
+        match phase {
+            1 => 30,
+            2 => 20,
+            _ => 15,
+        }
+    }
+    fn move_interval(&self, phase: u8) -> u32 {
+        match phase {
+            1 => 4,
+            2 => 3,
+            _ => 2,
+        }
+    }
+    fn step(&self, boss: &mut Boss, _player_x: i32) {
+        let (head_x, head_y) = (boss.parts[0].dx, boss.parts[0].dy);
+        let mut next = (head_x + boss.direction as i32, head_y);
+        if !(0..WIDTH as i32).contains(&next.0) {
+            // Turn around at the edges, dropping down until the bottom row and then
+            // climbing back up
+            let drop = 2 * boss.vertical_direction as i32;
+            if !(WORM_TOP..=WORM_BOTTOM).contains(&(head_y + drop)) {
+                boss.vertical_direction *= -1;
+            }
+            boss.direction *= -1;
+            next = (head_x, head_y + 2 * boss.vertical_direction as i32);
+        }
+        // Every part moves into the place of the one in front of it
+        for part in &mut boss.parts {
+            let here = (part.dx, part.dy);
+            (part.dx, part.dy) = next;
+            next = here;
+        }
+    }
+    fn fire(&self, boss: &Boss) -> Vec<(&'static str, Cell)> {
+        let head = (boss.parts[0].dx, boss.parts[0].dy + 1);
+        match boss.phase() {
+            // The head spits straight down
+            1 => vec![("down", head)],
+            // The tail joins in
+            2 => {
+                let mut volley = vec![("down", head)];
+                if let Some(tail) = boss.standing(PartKind::Segment).last() {
+                    volley.push(("down", (tail.dx, tail.dy + 1)));
+                }
+                volley
+            }
+            // The exposed head fires bursts at the player
+            _ => vec![("burst", head)],
+        }
+    }
+    fn core_exposed(&self, boss: &Boss) -> bool {
+        boss.phase() == 3
+    }
+}
+
+// Sprite width of the mirror, it keeps its middle over the column it is after
+const MIRROR_WIDTH: i32 = 9;
+
+impl BossKind for Mirror {
+    fn name(&self) -> &'static str {
+        "MIRROR"
+    }
+    fn intro(&self) -> &'static str {
+        "It copies your every move, backwards"
+    }
+    fn sprite(&self) -> &'static [&'static str] {
+        #[rustfmt::skip]
+        const SPRITE: &[&str] = &[
+            "  _/O\\_  ",
+            "<[=====]>",
+        ];
+        SPRITE
+    }
+    fn parts(&self, toughness: u16) -> Vec<Part> {
+        vec![
+            Part::new(PartKind::Turret, 0, 1, "<", 6 + 2 * toughness),
+            Part::new(PartKind::Turret, 8, 1, ">", 6 + 2 * toughness),
+            Part::new(PartKind::Shield, 1, 1, "[=====]", 12 + 4 * toughness),
+            Part::new(PartKind::Core, 4, 0, "O", 15 + 5 * toughness),
+        ]
+    }
+    fn start(&self) -> (i32, i32) {
+        ((WIDTH as i32 - MIRROR_WIDTH) / 2, 3)
+    }
+    fn fire_interval(&self, phase: u8) -> u8 {
+        match phase {
+            1 => 18,
+            2 => 14,
+            _ => 10,
+        }
+    }
+    fn move_interval(&self, phase: u8) -> u32 {
+        match phase {
+            1 | 2 => 3,
+            _ => 2,
+        }
+    }
+    // Mirror the player across the middle of the playfield, then once the turrets
+    // are gone stop pretending and follow them
+    fn step(&self, boss: &mut Boss, player_x: i32) {
+        let target = match boss.phase() {
+            1 => WIDTH as i32 - 1 - player_x,
+            _ => player_x,
+        };
+        let middle = boss.x + MIRROR_WIDTH / 2;
+        boss.x = (boss.x + (target - middle).signum()).clamp(0, WIDTH as i32 - MIRROR_WIDTH);
+    }
+    fn fire(&self, boss: &Boss) -> Vec<(&'static str, Cell)> {
+        let core = (boss.x + MIRROR_WIDTH / 2, boss.y + boss.height());
+        match boss.phase() {
+            1 => fire_from(boss, PartKind::Turret, "down"),
+            2 => vec![("spray", core)],
+            _ => vec![("spiral", core)],
+        }
+    }
+}
+
+impl BossKind for Tank {
+    fn name(&self) -> &'static str {
+        "SIEGE TANK"
+    }
+    fn intro(&self) -> &'static str {
+        "Break its armor to reach the twin cores"
+    }
+    fn sprite(&self) -> &'static [&'static str] {
+        #[rustfmt::skip]
+        const SPRITE: &[&str] = &[
+            " /=======\\ ",
+            "<|*=====*|>",
+            " \\##T=T##/ ",
+        ];
+        SPRITE
+    }
+    // Each core sits behind a slab of armor, the turrets sit between them
+    fn parts(&self, toughness: u16) -> Vec<Part> {
+        vec![
+            Part::new(PartKind::Core, 2, 1, "*", 10 + 3 * toughness),
+            Part::new(PartKind::Core, 8, 1, "*", 10 + 3 * toughness),
+            Part::new(PartKind::Shield, 2, 2, "##", 14 + 4 * toughness),
+            Part::new(PartKind::Shield, 7, 2, "##", 14 + 4 * toughness),
+            Part::new(PartKind::Turret, 4, 2, "T", 6 + 2 * toughness),
+            Part::new(PartKind::Turret, 6, 2, "T", 6 + 2 * toughness),
+        ]
+    }
+    fn start(&self) -> (i32, i32) {
+        ((WIDTH as i32 - 11) / 2, 3)
+    }
+    // Armored until a slab breaks, then angrier once the first core goes
+    fn phase(&self, boss: &Boss) -> u8 {
+        if boss.standing(PartKind::Shield).count() == 2 {
+            1
+        } else if boss.standing(PartKind::Core).count() == 2 {
+            2
+        } else {
+            3
+        }
+    }
+    fn fire_interval(&self, phase: u8) -> u8 {
+        match phase {
+            1 => 24,
+            2 => 18,
+            _ => 20,
+        }
+    }
+    fn move_interval(&self, phase: u8) -> u32 {
+        match phase {
+            1 => 16,
+            2 => 12,
+            _ => 8,
+        }
+    }
+    fn step(&self, boss: &mut Boss, _player_x: i32) {
+        slide(boss);
+    }
+    fn fire(&self, boss: &Boss) -> Vec<(&'static str, Cell)> {
+        match boss.phase() {
+            // Slow shells from the turrets
+            1 => fire_from(boss, PartKind::Turret, "shell"),
+            // Exposed cores aim at the player
+            2 => {
+                let mut volley = fire_from(boss, PartKind::Turret, "shell");
+                volley.extend(fire_from(boss, PartKind::Core, "aimed"));
+                volley
+            }
+            // A wall of shells with gaps to slip through
+            _ => vec![(
+                "shell-wall",
+                (boss.x + boss.width() / 2, boss.y + boss.height()),
+            )],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Patterns;
+
+    // Shoot `count` standing parts of a kind off
+    fn destroy(boss: &mut Boss, kind: PartKind, count: usize) {
+        for part in boss
+            .parts
+            .iter_mut()
+            .filter(|part| part.kind == kind && !part.is_destroyed())
+            .take(count)
+        {
+            part.health = 0;
+        }
+    }
+
+    #[test]
+    fn bosses_take_turns_every_fifth_level() {
+        let names: Vec<&str> = [5, 10, 15, 20, 25, 30]
+            .iter()
+            .map(|&level| for_level(level).name())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "MOTHERSHIP",
+                "THE WORM",
+                "MIRROR",
+                "SIEGE TANK",
+                "MOTHERSHIP",
+                "THE WORM"
+            ]
+        );
+        // Boss waves from a level file can come before level 5
+        assert_eq!(for_level(3).name(), "MOTHERSHIP");
+    }
+
+    #[test]
+    fn bosses_start_on_screen() {
+        for kind in ROSTER {
+            for level in [5, 50] {
+                assert!(Boss::new(kind, level).on_screen(), "{}", kind.name());
+            }
+        }
+    }
+
+    #[test]
+    fn every_volley_uses_a_bundled_pattern() {
+        let patterns = Patterns::bundled();
+        for kind in ROSTER {
+            let mut boss = Boss::new(kind, 5);
+            for kind_to_break in [PartKind::Turret, PartKind::Segment, PartKind::Shield] {
+                for (name, _) in boss.fire() {
+                    assert!(
+                        patterns.get(name).is_some(),
+                        "{} fires {}",
+                        kind.name(),
+                        name
+                    );
+                }
+                destroy(&mut boss, kind_to_break, usize::MAX);
+            }
+        }
+    }
+
+    #[test]
+    fn the_worm_angers_as_it_loses_segments() {
+        let mut boss = Boss::new(&Worm, 10);
+        let phases: Vec<u8> = (0..=WORM_SEGMENTS)
+            .map(|_| {
+                let phase = boss.phase();
+                destroy(&mut boss, PartKind::Segment, 1);
+                phase
+            })
+            .collect();
+        // Phase 2 below two thirds of the segments, phase 3 at a third
+        assert_eq!(phases, [1, 1, 1, 1, 2, 2, 2, 3, 3, 3, 3]);
+        assert!(Worm.core_exposed(&boss));
+    }
+
+    #[test]
+    fn the_tank_angers_as_its_armor_and_cores_go() {
+        let mut boss = Boss::new(&Tank, 20);
+        assert_eq!(boss.phase(), 1);
+        destroy(&mut boss, PartKind::Turret, usize::MAX);
+        assert_eq!(boss.phase(), 1);
+        destroy(&mut boss, PartKind::Shield, 1);
+        assert_eq!(boss.phase(), 2);
+        destroy(&mut boss, PartKind::Core, 1);
+        assert_eq!(boss.phase(), 3);
+        assert!(!boss.is_destroyed());
+        destroy(&mut boss, PartKind::Core, 2);
+        assert!(boss.is_destroyed());
+    }
+
+    #[test]
+    fn hangars_launch_every_few_volleys() {
+        let mut boss = Boss::new(&Mothership, 5);
+        let launches: Vec<usize> = (1..=LAUNCH_VOLLEYS * 2)
+            .map(|volleys| {
+                boss.volleys = volleys;
+                boss.launch().len()
+            })
+            .collect();
+        assert_eq!(launches, [0, 0, 0, 2, 0, 0, 0, 2]);
+        destroy(&mut boss, PartKind::Hangar, 1);
+        assert_eq!(boss.launch().len(), 1);
+    }
+}
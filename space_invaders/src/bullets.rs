@@ -0,0 +1,106 @@
+use termion::color;
+
+use crate::collision::{self, Cell};
+
+// A projectile with a sub-cell position and a velocity in cells per tick
+#[derive(Clone, Debug)]
+pub struct Bullet {
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    // Added to the velocity every tick, lets weapons curve
+    pub ax: f32,
+    pub ay: f32,
+    // Position before the last step, the bullet swept everything in between
+    pub last_x: f32,
+    pub last_y: f32,
+    pub is_enemy: bool,
+    pub owner: usize,           // Player who fired it, 0 for enemy bullets
+    pub fired_by: Option<char>, // Glyph of the enemy kind that fired it, None for bosses and players
+}
+
+impl Bullet {
+    pub fn new(x: i32, y: i32, vx: f32, vy: f32, is_enemy: bool) -> Self {
+        Bullet {
+            x: x as f32,
+            y: y as f32,
+            vx,
+            vy,
+            ax: 0.0,
+            ay: 0.0,
+            last_x: x as f32,
+            last_y: y as f32,
+            is_enemy,
+            owner: 0,
+            fired_by: None,
+        }
+    }
+
+    // Player bullet flying straight up
+    pub fn player(x: i32, y: i32) -> Self {
+        Bullet::new(x, y, 0.0, -1.0, false)
+    }
+
+    // Credit the bullet to a player, for their score
+    pub fn with_owner(mut self, owner: usize) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    // Change the velocity every tick, so the bullet curves
+    pub fn with_acceleration(mut self, ax: f32, ay: f32) -> Self {
+        self.ax = ax;
+        self.ay = ay;
+        self
+    }
+
+    // Advance one tick, `speed` scales how far the bullet moves
+    pub fn step(&mut self, speed: f32) {
+        self.last_x = self.x;
+        self.last_y = self.y;
+        self.vx += self.ax * speed;
+        self.vy += self.ay * speed;
+        self.x += self.vx * speed;
+        self.y += self.vy * speed;
+    }
+
+    // The world cell the bullet is in, which may be off-screen
+    pub fn cell(&self) -> (i32, i32) {
+        (self.x.round() as i32, self.y.round() as i32)
+    }
+
+    // Cells the bullet passed through during its last step
+    pub fn path(&self) -> Vec<Cell> {
+        collision::path((self.last_x, self.last_y), (self.x, self.y))
+    }
+
+    // Glyph that shows which way the bullet is flying
+    pub fn glyph(&self) -> char {
+        let sideways = self.vx.abs() > self.vy.abs() / 4.0;
+        if !self.is_enemy {
+            return match (sideways, self.vx > 0.0) {
+                (false, _) => '|',
+                (true, true) => '/',
+                (true, false) => '\\',
+            };
+        }
+        // Enemy bullets can fly any way a pattern sends them
+        match (sideways, self.vx > 0.0, self.vy < 0.0) {
+            (false, _, false) => '↓',
+            (false, _, true) => '↑',
+            (true, true, false) => '↘',
+            (true, false, false) => '↙',
+            (true, true, true) => '↗',
+            (true, false, true) => '↖',
+        }
+    }
+
+    pub fn color(&self) -> &'static dyn color::Color {
+        if self.is_enemy {
+            &color::Red
+        } else {
+            &color::Green
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::collision::Cell;
+use crate::world::PLAYER_ROW;
+use crate::WIDTH;
+
+// Bunkers are small walls above the player row, built from cells that wear down
+// one hit at a time. Bullets from either side chip a cell and stop, enemies that
+// walk into a bunker tear out every cell they pass. They are rebuilt each wave
+
+#[rustfmt::skip]
+const SHAPE: [&str; 3] = [
+    " ##### ",
+    "#######",
+    "##   ##",
+];
+pub const BUNKER_WIDTH: usize = 7;
+// Row of the top of each bunker, leaving room for the shield barrier below them
+const BUNKER_TOP: i32 = PLAYER_ROW - 5;
+// Hits a fresh cell takes before it crumbles, one glyph per health left
+const CELL_HEALTH: u8 = 4;
+const GLYPHS: [char; CELL_HEALTH as usize] = ['░', '▒', '▓', '█'];
+// Bunkers in waves that don't say otherwise
+const DEFAULT_COUNT: usize = 4;
+// The most bunkers that fit side by side with a gap between them
+pub const MAX_BUNKERS: usize = WIDTH / (BUNKER_WIDTH + 1);
+
+// Where a wave's bunkers go
+#[derive(Clone, Debug, PartialEq)]
+pub enum BunkerLayout {
+    Evenly(usize),  // This many, spread evenly across the playfield
+    At(Vec<usize>), // Left edges of each bunker
+}
+
+impl Default for BunkerLayout {
+    fn default() -> Self {
+        BunkerLayout::Evenly(DEFAULT_COUNT)
+    }
+}
+
+impl BunkerLayout {
+    // Left edge of every bunker
+    fn columns(&self) -> Vec<i32> {
+        match self {
+            BunkerLayout::Evenly(count) => (0..*count)
+                .map(|i| {
+                    let middle = WIDTH * (2 * i + 1) / (2 * count);
+                    middle as i32 - BUNKER_WIDTH as i32 / 2
+                })
+                .collect(),
+            BunkerLayout::At(columns) => columns.iter().map(|&x| x as i32).collect(),
+        }
+    }
+}
+
+// Every standing bunker cell as (x, y, health)
+#[derive(Default)]
+pub struct Bunkers {
+    cells: Vec<(i32, i32, u8)>,
+}
+
+impl Bunkers {
+    // Fresh bunkers laid out for a wave. Overlapping bunkers merge, and cells past
+    // the edges of the playfield are left out
+    pub fn build(layout: &BunkerLayout) -> Self {
+        let mut cells: Vec<(i32, i32, u8)> = Vec::new();
+        for left in layout.columns() {
+            for (dy, row) in SHAPE.iter().enumerate() {
+                for (dx, c) in row.chars().enumerate() {
+                    let (x, y) = (left + dx as i32, BUNKER_TOP + dy as i32);
+                    let on_playfield = (0..WIDTH as i32).contains(&x);
+                    if c != ' '
+                        && on_playfield
+                        && !cells.iter().any(|&(cx, cy, _)| (cx, cy) == (x, y))
+                    {
+                        cells.push((x, y, CELL_HEALTH));
+                    }
+                }
+            }
+        }
+        Bunkers { cells }
+    }
+
+    // Chip the first cell along a bullet's path. Returns whether the bullet hit one
+    pub fn hit(&mut self, path: &[Cell]) -> bool {
+        let index = path.iter().find_map(|&(x, y)| {
+            self.cells
+                .iter()
+                .position(|&(cx, cy, _)| (cx, cy) == (x, y))
+        });
+        match index {
+            Some(index) => {
+                self.cells[index].2 -= 1;
+                if self.cells[index].2 == 0 {
+                    self.cells.swap_remove(index);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Tear out every cell an enemy walked through
+    pub fn erase(&mut self, path: &[Cell]) {
+        self.cells.retain(|&(x, y, _)| !path.contains(&(x, y)));
+    }
+
+    // Every standing cell, with the glyph showing how worn down it is
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, char)> + '_ {
+        self.cells
+            .iter()
+            .map(|&(x, y, health)| (x, y, GLYPHS[health as usize - 1]))
+    }
+
+    // Cells with health outside 1..=CELL_HEALTH, for the fuzzer
+    pub fn worn_out(&self) -> Option<(i32, i32, u8)> {
+        self.cells
+            .iter()
+            .copied()
+            .find(|&(_, _, health)| health == 0 || health > CELL_HEALTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph_at(bunkers: &Bunkers, cell: Cell) -> Option<char> {
+        bunkers
+            .cells()
+            .find(|&(x, y, _)| (x, y) == cell)
+            .map(|(_, _, glyph)| glyph)
+    }
+
+    #[test]
+    fn cells_crumble_one_hit_at_a_time() {
+        let mut bunkers = Bunkers::build(&BunkerLayout::At(vec![10]));
+        let cell = (11, BUNKER_TOP);
+        let mut glyphs = Vec::new();
+        while let Some(glyph) = glyph_at(&bunkers, cell) {
+            glyphs.push(glyph);
+            assert!(bunkers.hit(&[cell]));
+        }
+        assert_eq!(glyphs, ['█', '▓', '▒', '░']);
+        // The cell is gone, bullets fly through
+        assert!(!bunkers.hit(&[cell]));
+        assert_eq!(bunkers.cells().count(), 15);
+        assert!(bunkers.worn_out().is_none());
+    }
+
+    #[test]
+    fn bullets_chip_the_first_cell_on_their_path() {
+        let mut bunkers = Bunkers::build(&BunkerLayout::At(vec![10]));
+        let upwards: Vec<Cell> = (BUNKER_TOP - 1..=BUNKER_TOP + 3)
+            .rev()
+            .map(|y| (10, y))
+            .collect();
+        assert!(bunkers.hit(&upwards));
+        assert_eq!(glyph_at(&bunkers, (10, BUNKER_TOP + 2)), Some('▓'));
+        assert_eq!(glyph_at(&bunkers, (10, BUNKER_TOP + 1)), Some('█'));
+        // Through the arch in the middle
+        assert!(!bunkers.hit(&[(13, BUNKER_TOP + 3), (13, BUNKER_TOP + 2)]));
+    }
+
+    #[test]
+    fn enemies_tear_out_their_whole_path() {
+        let mut bunkers = Bunkers::build(&BunkerLayout::At(vec![10]));
+        let row: Vec<Cell> = (0..WIDTH as i32).map(|x| (x, BUNKER_TOP + 1)).collect();
+        bunkers.erase(&row);
+        assert_eq!(bunkers.cells().count(), 9);
+        assert!(bunkers.cells().all(|(_, y, _)| y != BUNKER_TOP + 1));
+    }
+
+    #[test]
+    fn layouts_are_clipped_at_the_edges() {
+        let overhanging = BunkerLayout::At(vec![WIDTH - 3, WIDTH + 10]);
+        let bunkers = Bunkers::build(&overhanging);
+        let columns: Vec<i32> = bunkers.cells().map(|(x, _, _)| x).collect();
+        assert!(!columns.is_empty());
+        assert!(columns.iter().all(|x| (0..WIDTH as i32).contains(x)));
+
+        // More bunkers than fit squeeze together without running off either side
+        let crowded = Bunkers::build(&BunkerLayout::Evenly(50));
+        assert!(crowded.cells().any(|(x, _, _)| x == 0));
+        assert!(crowded
+            .cells()
+            .all(|(x, _, _)| (0..WIDTH as i32).contains(&x)));
+    }
+
+    #[test]
+    fn even_layouts_fit_side_by_side() {
+        for count in 0..=MAX_BUNKERS {
+            let bunkers = Bunkers::build(&BunkerLayout::Evenly(count));
+            assert_eq!(bunkers.cells().count(), 16 * count, "{} bunkers", count);
+        }
+    }
+}
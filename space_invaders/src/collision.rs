@@ -0,0 +1,96 @@
+use crate::Enemy;
+
+// A playfield cell. Paths can run off the playfield, so cells are signed
+pub type Cell = (i32, i32);
+
+// Cells on the straight line from one position to another, both ends included.
+// Collisions test every cell an object passed through during a tick, so things
+// that move more than one cell at a time can't skip over each other
+pub fn path(from: (f32, f32), to: (f32, f32)) -> Vec<Cell> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+    let mut cells: Vec<Cell> = Vec::with_capacity(steps + 1);
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let cell = (
+            (from.0 + dx * t).round() as i32,
+            (from.1 + dy * t).round() as i32,
+        );
+        if cells.last() != Some(&cell) {
+            cells.push(cell);
+        }
+    }
+    cells
+}
+
+// Cells an enemy passed through since its last collision check. Enemies that
+// wrapped around the screen or teleported only occupy the cell they landed on
+pub fn enemy_path(enemy: &Enemy) -> Vec<Cell> {
+    let (last_x, last_y) = enemy.last_position;
+    let jumped = (last_x - enemy.x).abs() > 2 || enemy.y < last_y;
+    if jumped {
+        return vec![(enemy.x, enemy.y)];
+    }
+    path(
+        (last_x as f32, last_y as f32),
+        (enemy.x as f32, enemy.y as f32),
+    )
+}
+
+// Whether two paths touch. `reach` widens the hit test sideways, so cells on the
+// same row count as touching when they are at most `reach` columns apart
+pub fn paths_meet(a: &[Cell], b: &[Cell], reach: i32) -> bool {
+    a.iter().any(|&(ax, ay)| {
+        b.iter()
+            .any(|&(bx, by)| ay == by && (ax - bx).abs() <= reach)
+    })
+}
+
+// Whether two objects that took turns to move touched. The first moved along `first`
+// while the other waited at the start of `second`, then the other moved along
+// `second` while the first waited at the end of its path. Matching whole paths
+// instead would count hits on cells one of them had already left
+pub fn turns_meet(first: &[Cell], second: &[Cell], reach: i32) -> bool {
+    let (Some(&stopped), Some(&waiting)) = (first.last(), second.first()) else {
+        return false;
+    };
+    paths_meet(first, &[waiting], reach) || paths_meet(&[stopped], second, reach)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_include_both_ends() {
+        assert_eq!(
+            path((2.0, 5.0), (2.0, 2.0)),
+            [(2, 5), (2, 4), (2, 3), (2, 2)]
+        );
+        assert_eq!(path((2.0, 5.0), (2.0, 5.0)), [(2, 5)]);
+    }
+
+    #[test]
+    fn bullets_miss_cells_an_enemy_has_left() {
+        // The enemy stepped from column 4 to 7, then the bullet flew up column 4
+        let enemy = path((4.0, 3.0), (7.0, 3.0));
+        let bullet = path((4.0, 5.0), (4.0, 2.0));
+        assert!(paths_meet(&enemy, &bullet, 1));
+        assert!(!turns_meet(&enemy, &bullet, 1));
+    }
+
+    #[test]
+    fn enemies_sweep_through_waiting_bullets() {
+        // An enemy dropping two rows passes the bullet, which then flies on away from it
+        let enemy = path((4.0, 3.0), (4.0, 5.0));
+        let bullet = path((4.0, 4.0), (4.0, 3.0));
+        assert!(turns_meet(&enemy, &bullet, 1));
+    }
+
+    #[test]
+    fn bullets_sweep_through_enemies_where_they_stopped() {
+        let enemy = path((6.0, 3.0), (5.0, 3.0));
+        assert!(turns_meet(&enemy, &path((4.0, 6.0), (4.0, 2.0)), 1));
+        assert!(!turns_meet(&enemy, &path((7.0, 6.0), (7.0, 2.0)), 1));
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+use std::time::Duration;
+use termion::color;
+use termion::event::Key;
+
+use crate::{Game, WIDTH};
+
+// A developer HUD to the right of the playfield, toggled with F3 during a game. It
+// shows the state of the tick on screen and how long that tick took to update and
+// draw, so an odd or slow frame can be pinned down while it happens
+
+pub const TOGGLE_KEY: Key = Key::F(3);
+// First column of the overlay, clear of the playfield
+const LEFT: u16 = WIDTH as u16 + 3;
+
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub shown: bool,
+    update_time: Duration,
+    render_time: Duration,
+}
+
+impl DebugOverlay {
+    // How long the tick on screen took
+    pub fn timed(&mut self, update_time: Duration, render_time: Duration) {
+        self.update_time = update_time;
+        self.render_time = render_time;
+    }
+
+    pub fn draw(&self, screen: &mut impl Write, game: &Game) -> io::Result<()> {
+        if !self.shown {
+            return Ok(());
+        }
+
+        // Player bullets are counted for each player, in co-op they fire their own
+        let mut bullets: Vec<String> = (0..game.players.len())
+            .map(|i| {
+                let count = game
+                    .bullets
+                    .iter()
+                    .filter(|bullet| !bullet.is_enemy && bullet.owner == i)
+                    .count();
+                format!("P{} {}", i + 1, count)
+            })
+            .collect();
+        let enemy_bullets = game.bullets.iter().filter(|bullet| bullet.is_enemy).count();
+        bullets.push(format!("enemy {}", enemy_bullets));
+        let boss = match &game.boss {
+            Some(boss) => format!("phase {} ({})", boss.phase(), boss.kind.name()),
+            None => "none".to_string(),
+        };
+
+        let mut lines = vec![
+            ("Tick", game.tick.to_string()),
+            ("Update", milliseconds(self.update_time)),
+            ("Render", milliseconds(self.render_time)),
+            ("Seed", game.seed.to_string()),
+            ("Enemies", game.enemies.len().to_string()),
+            ("Bullets", bullets.join(" / ")),
+            ("Queued", game.pending_bullets.len().to_string()),
+            ("Powerups", game.powerups.len().to_string()),
+            ("Explosions", game.explosions.len().to_string()),
+            (
+                "Move",
+                format!(
+                    "{}/{}",
+                    game.enemy_move_counter,
+                    game.enemy_move_interval() * game.slow_factor()
+                ),
+            ),
+            ("Boss", boss),
+        ];
+        for (i, player) in game.players.iter().enumerate() {
+            let powerup = match player.powerup_active {
+                Some(powerup) => format!(
+                    "P{} {} {}t ({:.1}s)",
+                    i + 1,
+                    powerup.name(),
+                    player.powerup_timer,
+                    player.powerup_time_left().as_secs_f32()
+                ),
+                None => format!("P{} none", i + 1),
+            };
+            lines.push(("Powerup", powerup));
+        }
+
+        write!(
+            screen,
+            "{}{}{}DEBUG (F3){}",
+            termion::cursor::Goto(LEFT, 2),
+            termion::style::Bold,
+            color::Fg(color::LightRed),
+            termion::style::Reset
+        )?;
+        for (row, (label, value)) in lines.into_iter().enumerate() {
+            write!(
+                screen,
+                "{}{}{:<11}{}{}{}",
+                termion::cursor::Goto(LEFT, 3 + row as u16),
+                color::Fg(color::LightBlack),
+                label,
+                color::Fg(color::White),
+                value,
+                color::Fg(color::Reset)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn milliseconds(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
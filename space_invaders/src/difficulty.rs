@@ -0,0 +1,43 @@
+// How hard the invaders push. Difficulty changes the pace of the waves, how often
+// enemies step and fire, and leaves everything else alone
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::ALL
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    // Ticks between enemy steps or shots, from what they would be on Normal
+    pub fn pace(self, ticks: usize) -> usize {
+        let percent = match self {
+            Difficulty::Easy => 150,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 70,
+        };
+        (ticks * percent / 100).max(1)
+    }
+
+    // Ticks between shots for an enemy that fires every `interval` ticks on Normal,
+    // `slow` times slower during Time Slow
+    pub fn fire_interval(self, interval: u8, slow: usize) -> u8 {
+        self.pace(interval as usize * slow).min(u8::MAX as usize) as u8
+    }
+}
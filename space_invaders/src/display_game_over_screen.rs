@@ -1,15 +1,32 @@
 use std::io::{self, Write};
 use std::time::Duration;
 use termion::color;
-use termion::screen::AlternateScreen;
 
+use crate::events::Stats;
+use crate::leaderboard::{Category, ENTRIES};
+
+// `scores` has one score per player, `leaderboard` the best scores for games with
+// that many players and `place` where this game landed on it, if it made it
 pub fn display_game_over_screen(
-    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
-    score: u32,
+    screen: &mut impl Write,
+    scores: &[u32],
     level: usize,
-    high_score: u32,
+    leaderboard: &[u32],
+    place: Option<usize>,
     time_survived: Duration,
+    stats: &Stats,
 ) -> io::Result<()> {
+    let total: u32 = scores.iter().sum();
+    let breakdown = if scores.len() > 1 {
+        let players: Vec<String> = scores
+            .iter()
+            .enumerate()
+            .map(|(i, score)| format!("P{} {}", i + 1, score))
+            .collect();
+        format!(" ({})", players.join(", "))
+    } else {
+        String::new()
+    };
     write!(screen, "{}", termion::clear::All)?;
     write!(
         screen,
@@ -21,10 +38,11 @@ pub fn display_game_over_screen(
     )?;
     write!(
         screen,
-        "{}{}Final Score: {}",
+        "{}{}Final Score: {}{}",
         termion::cursor::Goto(4, 9),
         color::Fg(color::Yellow),
-        score
+        total,
+        breakdown
     )?;
     write!(
         screen,
@@ -43,21 +61,54 @@ pub fn display_game_over_screen(
     )?;
     write!(
         screen,
-        "{}{}High Score: {}",
+        "{}{}Enemies Destroyed: {}",
+        termion::cursor::Goto(4, 12),
+        color::Fg(color::Yellow),
+        stats.enemies_destroyed
+    )?;
+    write!(
+        screen,
+        "{}{}Accuracy: {}% ({} of {} shots hit)",
         termion::cursor::Goto(4, 13),
+        color::Fg(color::Yellow),
+        stats.accuracy(),
+        stats.bullets_hit,
+        stats.bullets_fired
+    )?;
+    write!(
+        screen,
+        "{}{}Best {} scores:",
+        termion::cursor::Goto(4, 15),
         color::Fg(color::Cyan),
-        high_score
+        Category::for_players(scores.len()).name()
     )?;
+    for (i, score) in leaderboard.iter().enumerate() {
+        // This game's score stands out if it made the board
+        let row_color: &dyn color::Color = if place == Some(i + 1) {
+            &color::LightGreen
+        } else {
+            &color::Cyan
+        };
+        write!(
+            screen,
+            "{}{}{}. {}",
+            termion::cursor::Goto(6, 16 + i as u16),
+            color::Fg(row_color),
+            i + 1,
+            score
+        )?;
+    }
+    let prompt_row = 17 + ENTRIES as u16;
     write!(
         screen,
         "{}{}Press 'R' to play again",
-        termion::cursor::Goto(4, 15),
+        termion::cursor::Goto(4, prompt_row),
         color::Fg(color::Green)
     )?;
     write!(
         screen,
         "{}{}Press 'Q' to quit",
-        termion::cursor::Goto(4, 16),
+        termion::cursor::Goto(4, prompt_row + 1),
         color::Fg(color::Red)
     )?;
     screen.flush()?;
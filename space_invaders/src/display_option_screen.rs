@@ -1,10 +1,9 @@
 use std::io::{self, Write};
 use termion::color;
-use termion::screen::AlternateScreen;
 
-pub fn display_option_screen(
-    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
-) -> io::Result<()> {
+use crate::game_mode::GameMode;
+
+pub fn display_option_screen(screen: &mut impl Write, mode: GameMode) -> io::Result<()> {
     write!(screen, "{}", termion::clear::All)?;
     write!(
         screen,
@@ -36,6 +35,26 @@ pub fn display_option_screen(
         termion::cursor::Goto(28, 14),
         color::Fg(color::LightBlue)
     )?;
+    write!(
+        screen,
+        "{}{}Game Mode:",
+        termion::cursor::Goto(8, 16),
+        color::Fg(color::Yellow)
+    )?;
+    write!(
+        screen,
+        "{}{}M: {}",
+        termion::cursor::Goto(28, 16),
+        color::Fg(color::LightYellow),
+        mode.name()
+    )?;
+    write!(
+        screen,
+        "{}{}{}",
+        termion::cursor::Goto(8, 17),
+        color::Fg(color::White),
+        mode.description()
+    )?;
     write!(
         screen,
         "{}{}{}✰✰✰ O P T I O N S ✰✰✰{}",
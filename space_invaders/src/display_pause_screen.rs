@@ -1,13 +1,10 @@
 use std::io::{self, Write};
 use termion::color;
-use termion::screen::AlternateScreen;
 
 const WIDTH: usize = 80; // Example value, adjust as needed
 const HEIGHT: usize = 24; // Example value, adjust as needed
 
-pub fn display_pause_screen(
-    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
-) -> io::Result<()> {
+pub fn display_pause_screen(screen: &mut impl Write) -> io::Result<()> {
     write!(
         screen,
         "{}{}{}GAME PAUSED{}",
@@ -1,10 +1,7 @@
 use std::io::{self, Write};
 use termion::color;
-use termion::screen::AlternateScreen;
 
-pub fn display_start_screen(
-    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
-) -> io::Result<()> {
+pub fn display_start_screen(screen: &mut impl Write) -> io::Result<()> {
     write!(screen, "{}", termion::clear::All)?;
     write!(
         screen,
@@ -40,28 +37,46 @@ pub fn display_start_screen(
         termion::cursor::Goto(6, 15),
         color::Fg(color::Yellow)
     )?;
+    write!(
+        screen,
+        "{}{}Player 2: A/D to move, W to shoot",
+        termion::cursor::Goto(3, 17),
+        color::Fg(color::Magenta)
+    )?;
     write!(
         screen,
         "{}{}Press 'S' to start the game",
         termion::cursor::Goto(6, 21),
         color::Fg(color::Green)
     )?;
+    write!(
+        screen,
+        "{}{}Press '2' for two-player co-op",
+        termion::cursor::Goto(4, 22),
+        color::Fg(color::LightGreen)
+    )?;
     write!(
         screen,
         "{}{}Press 'T' for tutorial",
-        termion::cursor::Goto(9, 22),
+        termion::cursor::Goto(9, 23),
         color::Fg(color::Blue)
     )?;
     write!(
         screen,
         "{}{}Press 'O' for options",
-        termion::cursor::Goto(9, 23),
+        termion::cursor::Goto(9, 24),
         color::Fg(color::Magenta)
     )?;
+    write!(
+        screen,
+        "{}{}Press 'E' for level editor",
+        termion::cursor::Goto(7, 25),
+        color::Fg(color::LightCyan)
+    )?;
     write!(
         screen,
         "{}{}Press 'Q' to quit",
-        termion::cursor::Goto(11, 25),
+        termion::cursor::Goto(11, 27),
         color::Fg(color::Red)
     )?;
     screen.flush()?;
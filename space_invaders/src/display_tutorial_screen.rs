@@ -1,10 +1,10 @@
 use std::io::{self, Write};
 use termion::color;
-use termion::screen::AlternateScreen;
 
-pub fn display_tutorial_screen(
-    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
-) -> io::Result<()> {
+use crate::enemies::ALL_KINDS;
+use crate::powerups::Powerup;
+
+pub fn display_tutorial_screen(screen: &mut impl Write) -> io::Result<()> {
     write!(screen, "{}", termion::clear::All)?;
     write!(
         screen,
@@ -22,24 +22,17 @@ pub fn display_tutorial_screen(
         color::Fg(color::Yellow),
         color::Fg(color::Reset)
     )?;
-    write!(screen, "{}N - Normal enemy", termion::cursor::Goto(4, 5))?;
-    write!(screen, "{}Z - Zigzag enemy", termion::cursor::Goto(4, 6))?;
-    write!(screen, "{}W - Wave enemy", termion::cursor::Goto(4, 7))?;
-    write!(screen, "{}D - Diagonal enemy", termion::cursor::Goto(4, 8))?;
-    write!(
-        screen,
-        "{}H - Health enemy (gives extra life when destroyed)",
-        termion::cursor::Goto(4, 8)
-    )?;
-
-    write!(screen, "{}B - Bomber enemy", termion::cursor::Goto(4, 9))?;
-    write!(screen, "{}S - Shooter enemy", termion::cursor::Goto(4, 10))?;
-    write!(
-        screen,
-        "{}T - Teleporter enemy",
-        termion::cursor::Goto(4, 7)
-    )?;
-    write!(screen, "{}F - Rusher enemy", termion::cursor::Goto(4, 11))?;
+    for (i, kind) in ALL_KINDS.iter().enumerate() {
+        write!(
+            screen,
+            "{}{}{}{} - {}",
+            termion::cursor::Goto(4, 4 + i as u16),
+            color::Fg(kind.color()),
+            kind.glyph(),
+            color::Fg(color::Reset),
+            kind.description()
+        )?;
+    }
 
     write!(
         screen,
@@ -48,36 +41,32 @@ pub fn display_tutorial_screen(
         color::Fg(color::Yellow),
         color::Fg(color::Reset)
     )?;
-    write!(
-        screen,
-        "{}B - Bigger Laser (3-wide shot)",
-        termion::cursor::Goto(4, 15)
-    )?;
-    write!(
-        screen,
-        "{}M - Multi-directional Laser (3-way shot)",
-        termion::cursor::Goto(4, 16)
-    )?;
-    write!(
-        screen,
-        "{}S - Shield (temporary invincibility)",
-        termion::cursor::Goto(4, 17)
-    )?;
+    for (i, powerup) in Powerup::ALL.iter().enumerate() {
+        write!(
+            screen,
+            "{}{}{}{} - {}",
+            termion::cursor::Goto(4, 14 + i as u16),
+            color::Fg(powerup.color()),
+            powerup.glyph(),
+            color::Fg(color::Reset),
+            powerup.description()
+        )?;
+    }
 
     write!(
         screen,
         "{}{}Controls:{}",
-        termion::cursor::Goto(2, 19),
+        termion::cursor::Goto(2, 23),
         color::Fg(color::Yellow),
         color::Fg(color::Reset)
     )?;
     write!(
         screen,
         "{}Left/Right Arrow - Move ship",
-        termion::cursor::Goto(4, 21)
+        termion::cursor::Goto(4, 24)
     )?;
-    write!(screen, "{}Space - Shoot", termion::cursor::Goto(4, 22))?;
-    write!(screen, "{}P - Pause/Unpause", termion::cursor::Goto(4, 23))?;
+    write!(screen, "{}Space - Shoot", termion::cursor::Goto(4, 25))?;
+    write!(screen, "{}P - Pause/Unpause", termion::cursor::Goto(4, 26))?;
 
     write!(
         screen,
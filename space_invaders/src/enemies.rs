@@ -0,0 +1,264 @@
+use rand::{Rng, RngCore};
+use termion::color;
+
+use crate::world;
+use crate::{Enemy, HEIGHT, WIDTH};
+
+// Everything that makes one type of enemy different from another. Adding an enemy
+// means implementing this for a new type and listing it in ALL_KINDS
+pub trait EnemyKind: Sync {
+    // Character drawn on the playfield and used in level files
+    fn glyph(&self) -> char;
+    fn color(&self) -> &'static dyn color::Color;
+    // Shown on the tutorial screen
+    fn description(&self) -> &'static str;
+
+    fn spawn_health(&self) -> u8 {
+        1
+    }
+
+    fn score_value(&self) -> u32 {
+        10
+    }
+
+    // Lives given to the player when this enemy is destroyed
+    fn bonus_lives(&self) -> usize {
+        0
+    }
+
+    // Whether random formations may contain this enemy
+    fn in_random_formations(&self) -> bool {
+        true
+    }
+
+    // Move one step, called every time the enemies advance
+    fn step(&self, enemy: &mut Enemy, _rng: &mut dyn RngCore) {
+        enemy.y += 1;
+    }
+
+    // Ticks between shots, None for enemies that never shoot
+    fn fire_interval(&self) -> Option<u8> {
+        None
+    }
+
+    // Name of the bullet pattern fired in one shot, see patterns.txt
+    fn pattern(&self) -> &'static str {
+        "down"
+    }
+}
+
+pub struct Normal;
+pub struct Zigzag;
+pub struct Waver;
+pub struct Diagonal;
+pub struct Shooter;
+pub struct Teleporter;
+pub struct Rusher;
+pub struct Bomber;
+pub struct Health;
+
+// Every enemy kind, in the order the tutorial lists them
+pub const ALL_KINDS: [&dyn EnemyKind; 9] = [
+    &Normal,
+    &Zigzag,
+    &Waver,
+    &Diagonal,
+    &Health,
+    &Bomber,
+    &Shooter,
+    &Teleporter,
+    &Rusher,
+];
+
+// Look up the enemy kind drawn with a glyph
+pub fn kind_for(glyph: char) -> Option<&'static dyn EnemyKind> {
+    ALL_KINDS.iter().copied().find(|kind| kind.glyph() == glyph)
+}
+
+// Pick an enemy kind for a random formation
+pub fn random_kind(rng: &mut impl Rng) -> &'static dyn EnemyKind {
+    let kinds: Vec<&'static dyn EnemyKind> = ALL_KINDS
+        .iter()
+        .copied()
+        .filter(|kind| kind.in_random_formations())
+        .collect();
+    kinds[rng.gen_range(0..kinds.len())]
+}
+
+impl EnemyKind for Normal {
+    fn glyph(&self) -> char {
+        'N'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::White
+    }
+    fn description(&self) -> &'static str {
+        "Normal enemy"
+    }
+}
+
+impl EnemyKind for Zigzag {
+    fn glyph(&self) -> char {
+        'Z'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::LightRed
+    }
+    fn description(&self) -> &'static str {
+        "Zigzag enemy"
+    }
+    fn step(&self, enemy: &mut Enemy, _rng: &mut dyn RngCore) {
+        enemy.x = world::wrap_x(enemy.x + if enemy.y.rem_euclid(4) < 2 { 1 } else { -1 });
+        enemy.y += 1;
+    }
+}
+
+impl EnemyKind for Waver {
+    fn glyph(&self) -> char {
+        'W'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::LightMagenta
+    }
+    fn description(&self) -> &'static str {
+        "Wave enemy"
+    }
+    fn step(&self, enemy: &mut Enemy, _rng: &mut dyn RngCore) {
+        enemy.x = world::wrap_x(enemy.x + (enemy.y as f32 / 2.0).sin() as i32 + 1);
+        enemy.y += 1;
+    }
+}
+
+impl EnemyKind for Diagonal {
+    fn glyph(&self) -> char {
+        'D'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::LightYellow
+    }
+    fn description(&self) -> &'static str {
+        "Diagonal enemy"
+    }
+    fn step(&self, enemy: &mut Enemy, _rng: &mut dyn RngCore) {
+        enemy.x = world::wrap_x(enemy.x + 1);
+        enemy.y += 1;
+    }
+}
+
+impl EnemyKind for Shooter {
+    fn glyph(&self) -> char {
+        'S'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::Red
+    }
+    fn description(&self) -> &'static str {
+        "Shooter enemy"
+    }
+    fn spawn_health(&self) -> u8 {
+        2
+    }
+    fn score_value(&self) -> u32 {
+        20
+    }
+    fn fire_interval(&self) -> Option<u8> {
+        Some(50)
+    }
+    // Shooters aim at the player
+    fn pattern(&self) -> &'static str {
+        "aimed"
+    }
+}
+
+impl EnemyKind for Teleporter {
+    fn glyph(&self) -> char {
+        'T'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::Cyan
+    }
+    fn description(&self) -> &'static str {
+        "Teleporter enemy"
+    }
+    fn spawn_health(&self) -> u8 {
+        2
+    }
+    fn score_value(&self) -> u32 {
+        20
+    }
+    fn step(&self, enemy: &mut Enemy, rng: &mut dyn RngCore) {
+        if rng.gen_bool(0.1) {
+            enemy.x = rng.gen_range(0..WIDTH as i32);
+            enemy.y = rng.gen_range(0..HEIGHT as i32 / 2);
+        } else {
+            enemy.y += 1;
+        }
+    }
+}
+
+impl EnemyKind for Rusher {
+    fn glyph(&self) -> char {
+        'F'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::LightBlue
+    }
+    fn description(&self) -> &'static str {
+        "Rusher enemy"
+    }
+    fn spawn_health(&self) -> u8 {
+        2
+    }
+    fn score_value(&self) -> u32 {
+        20
+    }
+    fn step(&self, enemy: &mut Enemy, _rng: &mut dyn RngCore) {
+        enemy.y += 2;
+    }
+}
+
+impl EnemyKind for Bomber {
+    fn glyph(&self) -> char {
+        'B'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::LightCyan
+    }
+    fn description(&self) -> &'static str {
+        "Bomber enemy"
+    }
+    fn spawn_health(&self) -> u8 {
+        3
+    }
+    fn score_value(&self) -> u32 {
+        30
+    }
+    fn fire_interval(&self) -> Option<u8> {
+        Some(50)
+    }
+    // Bomber drops 3 bombs that spread out and speed up as they fall
+    fn pattern(&self) -> &'static str {
+        "bomber"
+    }
+}
+
+impl EnemyKind for Health {
+    fn glyph(&self) -> char {
+        'H'
+    }
+    fn color(&self) -> &'static dyn color::Color {
+        &color::Green
+    }
+    fn description(&self) -> &'static str {
+        "Health enemy (gives extra life when destroyed)"
+    }
+    fn score_value(&self) -> u32 {
+        50
+    }
+    fn bonus_lives(&self) -> usize {
+        1
+    }
+    fn in_random_formations(&self) -> bool {
+        false
+    }
+}
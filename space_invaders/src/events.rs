@@ -0,0 +1,71 @@
+use crate::powerups::Powerup;
+
+// Something that happened during a tick. The game queues these and the game
+// loop drains them to play sounds and keep stats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    Shot { bullets: usize },
+    EnemyHit { destroyed: bool },
+    BossHit { destroyed: bool },
+    ShipCollision, // An enemy crashed into the ship
+    ShieldBlock,
+    PlayerHit(Cause), // The player lost a life
+    PowerupCollected(Powerup),
+    UfoAppeared,
+    UfoHit { points: u32 },
+}
+
+// Totals for one game, shown on the game over screen
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub bullets_fired: u32,
+    pub bullets_hit: u32,
+    pub enemies_destroyed: u32,
+}
+
+impl Stats {
+    pub fn record(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::Shot { bullets } => self.bullets_fired += bullets as u32,
+            GameEvent::EnemyHit { destroyed } => {
+                self.bullets_hit += 1;
+                if destroyed {
+                    self.enemies_destroyed += 1;
+                }
+            }
+            GameEvent::BossHit { .. } | GameEvent::UfoHit { .. } => self.bullets_hit += 1,
+            GameEvent::ShipCollision => self.enemies_destroyed += 1,
+            GameEvent::ShieldBlock
+            | GameEvent::PlayerHit(_)
+            | GameEvent::PowerupCollected(_)
+            | GameEvent::UfoAppeared => {}
+        }
+    }
+
+    // Percentage of fired bullets that hit something
+    pub fn accuracy(&self) -> u32 {
+        (self.bullets_hit * 100)
+            .checked_div(self.bullets_fired)
+            .unwrap_or(0)
+    }
+}
+
+// What cost a player a life. Enemies go by the glyph of their kind
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cause {
+    Shot(char),
+    BossShot,
+    Rammed(char),   // The enemy crashed into the ship
+    Invasion(char), // The enemy reached the bottom row
+}
+
+impl Cause {
+    pub fn label(self) -> String {
+        match self {
+            Cause::Shot(glyph) => format!("shot by {}", glyph),
+            Cause::BossShot => "shot by the boss".to_string(),
+            Cause::Rammed(glyph) => format!("rammed by {}", glyph),
+            Cause::Invasion(glyph) => format!("invasion by {}", glyph),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{Enemy, WIDTH};
+
+// Classic mode moves the wave as one block. It slides sideways a column at a time,
+// and when any member reaches an edge the whole block steps down a row and turns
+// around. Enemies that dropped in outside the block move with it but don't turn it,
+// they wait at an edge instead of running off. Only the bottom-most enemy of each
+// column can fire
+
+// Ticks between formation steps. The block starts at twice the arcade pace and
+// speeds up as members die, until the last one moves every tick
+pub fn step_interval(base: usize, alive: usize, size: usize) -> usize {
+    if size == 0 {
+        return 1;
+    }
+    (base * alive.min(size)).div_ceil(2 * size).max(1)
+}
+
+// Move the formation one step. Returns the direction it moves in next
+pub fn march(enemies: &mut [Enemy], direction: i32) -> i32 {
+    let fits = |enemy: &Enemy| (0..WIDTH as i32).contains(&(enemy.x + direction));
+    let at_edge = enemies
+        .iter()
+        .any(|enemy| enemy.in_formation && !fits(enemy));
+    if at_edge {
+        for enemy in enemies.iter_mut() {
+            enemy.y += 1;
+        }
+        -direction
+    } else {
+        for enemy in enemies.iter_mut().filter(|enemy| fits(enemy)) {
+            enemy.x += direction;
+        }
+        direction
+    }
+}
+
+// Indexes of the bottom-most enemy in each column
+pub fn shooters(enemies: &[Enemy]) -> Vec<usize> {
+    let mut bottom: Vec<usize> = Vec::new();
+    for (index, enemy) in enemies.iter().enumerate() {
+        match bottom
+            .iter_mut()
+            .find(|other| enemies[**other].x == enemy.x)
+        {
+            Some(other) if enemies[*other].y < enemy.y => *other = index,
+            Some(_) => {}
+            None => bottom.push(index),
+        }
+    }
+    bottom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemies;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn block(columns: &[i32]) -> Vec<Enemy> {
+        let mut rng = StdRng::seed_from_u64(0);
+        columns
+            .iter()
+            .map(|&x| Enemy::new(x, 3, &enemies::Normal, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn block_turns_at_an_edge() {
+        let mut enemies = block(&[WIDTH as i32 - 3, WIDTH as i32 - 1]);
+        assert_eq!(march(&mut enemies, 1), -1);
+        assert!(enemies.iter().all(|enemy| enemy.y == 4));
+        assert_eq!(enemies[1].x, WIDTH as i32 - 1);
+    }
+
+    #[test]
+    fn drop_ins_at_an_edge_dont_turn_the_block() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemies = block(&[20, 22, WIDTH as i32 - 1]);
+        enemies[2].in_formation = false;
+        let mut straggler = Enemy::drop_in(&mut rng);
+        straggler.x = 0;
+        enemies.push(straggler);
+
+        // Heading left, the block slides while the drop-ins stay on screen
+        assert_eq!(march(&mut enemies, -1), -1);
+        let columns: Vec<i32> = enemies.iter().map(|enemy| enemy.x).collect();
+        assert_eq!(columns, [19, 21, WIDTH as i32 - 2, 0]);
+        assert_eq!(enemies[0].y, 3);
+    }
+}
@@ -0,0 +1,446 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use termion::event::Key;
+
+use crate::game_mode::GameMode;
+use crate::levels::Wave;
+use crate::patterns::Patterns;
+use crate::world;
+use crate::{
+    find_patterns, load_level_file, Game, DEFAULT_LEVEL_FILE, DEFAULT_PATTERN_FILE,
+    POWERUP_DURATION_TICKS,
+};
+
+// The fuzzer plays games headlessly, pressing random keys, and checks the game's
+// invariants after every tick. A game that breaks one, or panics, is written out
+// as a case file holding its seed and every key pressed, which replays it exactly
+//
+//   space_invaders fuzz [--seed <n>] [--ticks <n>] [--levels <file>] [--patterns <file>]
+//   space_invaders fuzz --replay <case file>
+
+const DEFAULT_TICKS: u64 = 1_000_000;
+// Long games are cut off so their case files stay small
+const MAX_GAME_TICKS: u64 = 100_000;
+// Rendering costs far more than a tick, so only every so often is drawn
+const RENDER_INTERVAL: u64 = 100;
+
+// One game: the seed for its randomness and the keys pressed, by tick
+struct Case {
+    seed: u64,
+    level_file: Option<String>,
+    pattern_file: Option<String>,
+    inputs: Vec<(u64, Key)>,
+}
+
+// A broken invariant or a panic
+struct Failure {
+    tick: u64,
+    message: String,
+}
+
+pub fn run(args: Vec<String>) -> io::Result<()> {
+    let mut seed = None;
+    let mut total_ticks = DEFAULT_TICKS;
+    let mut level_file = None;
+    let mut pattern_file = None;
+    let mut replay_file = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--seed", Some(value)) => seed = Some(parse_number(&arg, &value)),
+            ("--ticks", Some(value)) => total_ticks = parse_number(&arg, &value),
+            ("--levels", Some(path)) => level_file = Some(path),
+            ("--patterns", Some(path)) => pattern_file = Some(path),
+            ("--replay", Some(path)) => replay_file = Some(path),
+            ("--seed" | "--ticks" | "--levels" | "--patterns" | "--replay", None) => {
+                eprintln!("{} needs a value", arg);
+                std::process::exit(2);
+            }
+            _ => {
+                eprintln!("Unknown fuzz option: {}", arg);
+                eprintln!(
+                    "Usage: space_invaders fuzz [--seed <n>] [--ticks <n>] [--levels <file>] [--patterns <file>]"
+                );
+                eprintln!("       space_invaders fuzz --replay <case file>");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Panics are reported as failures, keep the default hook from printing them too
+    panic::set_hook(Box::new(|_| {}));
+
+    if let Some(path) = replay_file {
+        return replay(&path);
+    }
+
+    // Use the same waves the game would
+    let level_file = match level_file {
+        Some(path) => Some(path),
+        None if Path::new(DEFAULT_LEVEL_FILE).exists() => Some(DEFAULT_LEVEL_FILE.to_string()),
+        None => None,
+    };
+    let waves = level_file
+        .as_deref()
+        .map(load_level_file)
+        .unwrap_or_default();
+    let pattern_file = match pattern_file {
+        Some(path) => Some(path),
+        None if Path::new(DEFAULT_PATTERN_FILE).exists() => Some(DEFAULT_PATTERN_FILE.to_string()),
+        None => None,
+    };
+    let patterns = find_patterns(pattern_file.as_deref());
+
+    let base_seed = seed.unwrap_or_else(rand::random);
+    println!("Fuzzing {} ticks from seed {}", total_ticks, base_seed);
+    let mut ticks_played = 0;
+    let mut games = 0;
+    while ticks_played < total_ticks {
+        let mut case = Case {
+            seed: base_seed.wrapping_add(games),
+            level_file: level_file.clone(),
+            pattern_file: pattern_file.clone(),
+            inputs: Vec::new(),
+        };
+        let max_ticks = MAX_GAME_TICKS.min(total_ticks - ticks_played);
+        let mut keys = StdRng::seed_from_u64(case.seed ^ 0x5eed);
+        let mut inputs = Vec::new();
+        let result = play(case.seed, &waves, &patterns, max_ticks, |tick| {
+            let key = random_key(&mut keys)?;
+            inputs.push((tick, key));
+            Some(key)
+        });
+        case.inputs = inputs;
+        games += 1;
+        match result {
+            Ok(ticks) => ticks_played += ticks,
+            Err(failure) => {
+                let path = format!("fuzz-{}.case", case.seed);
+                fs::write(&path, format_case(&case, &failure))?;
+                println!(
+                    "Game with seed {} failed at tick {}: {}",
+                    case.seed, failure.tick, failure.message
+                );
+                println!(
+                    "Wrote {}, replay it with: space_invaders fuzz --replay {}",
+                    path, path
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+    println!(
+        "Played {} ticks over {} games, every invariant held",
+        ticks_played, games
+    );
+    Ok(())
+}
+
+// Play a case file back and report whether it still fails
+fn replay(path: &str) -> io::Result<()> {
+    let source = fs::read_to_string(path)?;
+    let case = match parse_case(&source) {
+        Ok(case) => case,
+        Err(message) => {
+            eprintln!("Error in case file {}: {}", path, message);
+            std::process::exit(2);
+        }
+    };
+    let waves = case
+        .level_file
+        .as_deref()
+        .map(load_level_file)
+        .unwrap_or_default();
+    let patterns = find_patterns(case.pattern_file.as_deref());
+    let mut inputs = case.inputs.iter().peekable();
+    let result = play(case.seed, &waves, &patterns, MAX_GAME_TICKS, |tick| {
+        inputs.next_if(|&&(at, _)| at == tick).map(|&(_, key)| key)
+    });
+    match result {
+        Ok(ticks) => println!("Case passed after {} ticks", ticks),
+        Err(failure) => {
+            println!("Case failed at tick {}: {}", failure.tick, failure.message);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+// Play one game until it is over or `max_ticks` have passed. `input` is asked for
+// a key before every tick. Returns the number of ticks played
+fn play(
+    seed: u64,
+    waves: &[Wave],
+    patterns: &Patterns,
+    max_ticks: u64,
+    mut input: impl FnMut(u64) -> Option<Key>,
+) -> Result<u64, Failure> {
+    let mut game = Game::with_seed(seed);
+    // Odd seeds play classic mode and every other pair of seeds is co-op, so the
+    // seed alone picks the mode and players when replaying
+    game.mode = GameMode::ALL[(seed % 2) as usize];
+    game.set_player_count(1 + (seed / 2 % 2) as usize);
+    game.waves = waves.to_vec();
+    game.patterns = patterns.clone();
+    game.start_level();
+
+    for tick in 0..max_ticks {
+        let key = input(tick);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Some(key) = key {
+                game.handle_input(key);
+            }
+            game.update();
+            for event in game.events.drain(..) {
+                game.stats.record(&event);
+            }
+            if tick % RENDER_INTERVAL == 0 {
+                game.render();
+            }
+        }));
+        if let Err(payload) = result {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            return Err(Failure {
+                tick,
+                message: format!("panicked: {}", message),
+            });
+        }
+        check_invariants(&game).map_err(|message| Failure { tick, message })?;
+        if game.is_game_over() {
+            return Ok(tick + 1);
+        }
+    }
+    Ok(max_ticks)
+}
+
+// Roughly one key every few ticks for either player, pausing only now and then.
+// Player two's keys do nothing in solo games
+fn random_key(rng: &mut StdRng) -> Option<Key> {
+    if !rng.gen_bool(0.3) {
+        return None;
+    }
+    Some(match rng.gen_range(0..100) {
+        0..=17 => Key::Left,
+        18..=34 => Key::Char('a'),
+        35..=51 => Key::Right,
+        52..=69 => Key::Char('d'),
+        70..=83 => Key::Char(' '),
+        84..=97 => Key::Char('w'),
+        _ => Key::Char('p'),
+    })
+}
+
+// Things that must hold after every tick
+fn check_invariants(game: &Game) -> Result<(), String> {
+    for (i, player) in game.players.iter().enumerate() {
+        if !world::on_screen(player.x, world::PLAYER_ROW) {
+            return Err(format!(
+                "player {} is off the playfield at column {}",
+                i + 1,
+                player.x
+            ));
+        }
+        if player.lives > 5 {
+            return Err(format!(
+                "player {} has {} lives, the most is 5",
+                i + 1,
+                player.lives
+            ));
+        }
+        if let Some(powerup) = player.powerup_active {
+            if powerup.is_instant() || player.powerup_timer > POWERUP_DURATION_TICKS {
+                return Err(format!(
+                    "player {} has {} powerup active with {} ticks left",
+                    i + 1,
+                    powerup.name(),
+                    player.powerup_timer
+                ));
+            }
+        }
+    }
+    for bullet in game.bullets.iter().filter(|bullet| !bullet.is_enemy) {
+        if bullet.owner >= game.players.len() {
+            return Err(format!(
+                "bullet belongs to missing player {}",
+                bullet.owner + 1
+            ));
+        }
+    }
+    for enemy in &game.enemies {
+        if !world::on_screen(enemy.x, enemy.y) {
+            return Err(format!(
+                "{} enemy is off the playfield at ({}, {})",
+                enemy.kind.glyph(),
+                enemy.x,
+                enemy.y
+            ));
+        }
+        if enemy.health == 0 || enemy.health > enemy.kind.spawn_health() {
+            return Err(format!(
+                "{} enemy has {} health, it spawns with {}",
+                enemy.kind.glyph(),
+                enemy.health,
+                enemy.kind.spawn_health()
+            ));
+        }
+    }
+    for bullet in &game.bullets {
+        let (x, y) = bullet.cell();
+        // Bullets fired this tick haven't moved yet, the despawn rules see them next tick
+        let just_fired = bullet.x == bullet.last_x && bullet.y == bullet.last_y;
+        if !world::on_screen(x, y) && !just_fired {
+            return Err(format!("bullet was not despawned at ({}, {})", x, y));
+        }
+    }
+    for &(x, y, powerup) in &game.powerups {
+        if !world::on_screen(x, y) {
+            return Err(format!(
+                "{} powerup is off the playfield at ({}, {})",
+                powerup.name(),
+                x,
+                y
+            ));
+        }
+    }
+    for (x, y, _) in game.bunkers.cells() {
+        if !world::on_screen(x, y) || y >= world::PLAYER_ROW - 1 {
+            return Err(format!("bunker cell is out of place at ({}, {})", x, y));
+        }
+    }
+    if let Some(ufo) = &game.ufo {
+        if !ufo.on_screen() {
+            return Err(format!("UFO was not despawned at column {}", ufo.x));
+        }
+    }
+    if let Some((x, y, health)) = game.bunkers.worn_out() {
+        return Err(format!(
+            "bunker cell at ({}, {}) has {} health",
+            x, y, health
+        ));
+    }
+    if let Some(boss) = &game.boss {
+        for part in &boss.parts {
+            if part.health > part.max_health {
+                return Err(format!(
+                    "boss {:?} has {} of {} health",
+                    part.kind, part.health, part.max_health
+                ));
+            }
+        }
+        if boss.is_destroyed() {
+            return Err("boss without a core was not removed".to_string());
+        }
+        if !(1..=3).contains(&boss.phase()) {
+            return Err(format!("{} is in phase {}", boss.kind.name(), boss.phase()));
+        }
+        if !(0.0..=1.0).contains(&boss.health_fraction()) {
+            return Err(format!(
+                "boss health bar is out of bounds at {}",
+                boss.health_fraction()
+            ));
+        }
+        if !boss.on_screen() {
+            return Err(format!(
+                "boss is off the playfield at ({}, {})",
+                boss.x, boss.y
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_number(option: &str, value: &str) -> u64 {
+    match value.parse() {
+        Ok(number) => number,
+        Err(_) => {
+            eprintln!("{} needs a number, got '{}'", option, value);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn key_name(key: Key) -> &'static str {
+    match key {
+        Key::Left => "left",
+        Key::Right => "right",
+        Key::Char(' ') => "space",
+        Key::Char('a') => "a",
+        Key::Char('d') => "d",
+        Key::Char('w') => "w",
+        _ => "pause",
+    }
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    match name {
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "space" => Some(Key::Char(' ')),
+        "a" => Some(Key::Char('a')),
+        "d" => Some(Key::Char('d')),
+        "w" => Some(Key::Char('w')),
+        "pause" => Some(Key::Char('p')),
+        _ => None,
+    }
+}
+
+// Case files are line based: the seed, the level and pattern files if there were
+// any, then one '<tick> <key>' line per key pressed. Lines starting with '#' are
+// comments
+fn format_case(case: &Case, failure: &Failure) -> String {
+    let mut output = String::from("# space_invaders fuzz case\n");
+    output.push_str(&format!(
+        "# failed at tick {}: {}\n",
+        failure.tick, failure.message
+    ));
+    output.push_str(&format!("seed {}\n", case.seed));
+    if let Some(path) = &case.level_file {
+        output.push_str(&format!("levels {}\n", path));
+    }
+    if let Some(path) = &case.pattern_file {
+        output.push_str(&format!("patterns {}\n", path));
+    }
+    for &(tick, key) in &case.inputs {
+        output.push_str(&format!("{} {}\n", tick, key_name(key)));
+    }
+    output
+}
+
+fn parse_case(source: &str) -> Result<Case, String> {
+    let mut seed = None;
+    let mut level_file = None;
+    let mut pattern_file = None;
+    let mut inputs = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || format!("line {}: can't read '{}'", index + 1, line);
+        let (first, rest) = line.split_once(' ').ok_or_else(error)?;
+        match first {
+            "seed" => seed = Some(rest.parse().map_err(|_| error())?),
+            "levels" => level_file = Some(rest.to_string()),
+            "patterns" => pattern_file = Some(rest.to_string()),
+            tick => {
+                let tick: u64 = tick.parse().map_err(|_| error())?;
+                inputs.push((tick, key_from_name(rest).ok_or_else(error)?));
+            }
+        }
+    }
+    Ok(Case {
+        seed: seed.ok_or("missing 'seed' line")?,
+        level_file,
+        pattern_file,
+        inputs,
+    })
+}
@@ -0,0 +1,40 @@
+// How the enemies of a wave behave. Arcade lets every enemy move and fire its own
+// way, Classic marches the wave as one formation like the original arcade game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    Arcade,
+    Classic,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Arcade, GameMode::Classic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Arcade => "Arcade",
+            GameMode::Classic => "Classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        GameMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            GameMode::Arcade => "Every enemy moves and fires its own way",
+            GameMode::Classic => "The wave marches as one and speeds up as it shrinks",
+        }
+    }
+
+    // The mode after this one, for switching on the options screen
+    pub fn next(self) -> GameMode {
+        match self {
+            GameMode::Arcade => GameMode::Classic,
+            GameMode::Classic => GameMode::Arcade,
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::fmt;
+
+// Just enough JSON for the machine-facing modes: a value type that prints as
+// compact JSON, and a parser for the small commands sent back. Numbers are f64,
+// except whole numbers without a sign, which are kept exact so big seeds survive
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Integer(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>), // Keys stay in the order they were added
+}
+
+impl Json {
+    // An object from key and value pairs
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            Json::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    // Whole numbers that fit a u64, like seeds. A number written with a fraction or
+    // exponent only counts below 2^53, from there on the f64 may have been rounded
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Integer(n) => Some(*n),
+            Json::Number(n) if n.fract() == 0.0 && *n >= 0.0 && *n < EXACT_F64 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            at: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_space();
+        if parser.at < parser.chars.len() {
+            return Err(format!(
+                "unexpected '{}' after the value",
+                parser.chars[parser.at]
+            ));
+        }
+        Ok(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+macro_rules! json_number {
+    ($variant:ident as $as:ty: $($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(n: $t) -> Self {
+                Json::$variant(n as $as)
+            }
+        })*
+    };
+}
+json_number!(Number as f64: i32, f32, f64);
+json_number!(Integer as u64: u8, u16, u32, u64, usize);
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            // JSON has no infinities or NaN
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::Integer(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// Every whole number below this is exact as an f64, 2^53 itself may be 2^53 + 1
+// rounded down
+const EXACT_F64: f64 = (1u64 << 53) as f64;
+
+// Arrays and objects nested deeper than this are refused, the parser recurses and a
+// line of brackets would otherwise overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    depth: usize, // Arrays and objects open around the current value
+}
+
+impl Parser {
+    fn skip_space(&mut self) {
+        while self.chars.get(self.at).is_some_and(|c| c.is_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.at).copied();
+        self.at += 1;
+        c
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        for expected in word.chars() {
+            if self.next() != Some(expected) {
+                return Err(format!("expected '{}'", word));
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_space();
+        match self.chars.get(self.at) {
+            None => Err("unexpected end of input".to_string()),
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[' | '{') => {
+                if self.depth >= MAX_DEPTH {
+                    return Err("too deeply nested".to_string());
+                }
+                self.depth += 1;
+                let value = self.container();
+                self.depth -= 1;
+                value
+            }
+            Some(_) => self.number(),
+        }
+    }
+
+    // An array or an object, the parser is on its opening bracket
+    fn container(&mut self) -> Result<Json, String> {
+        match self.chars.get(self.at) {
+            Some('[') => {
+                self.at += 1;
+                let mut values = Vec::new();
+                self.skip_space();
+                if self.chars.get(self.at) == Some(&']') {
+                    self.at += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_space();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Json::Array(values)),
+                        _ => return Err("expected ',' or ']' in an array".to_string()),
+                    }
+                }
+            }
+            Some('{') => {
+                self.at += 1;
+                let mut fields = Vec::new();
+                self.skip_space();
+                if self.chars.get(self.at) == Some(&'}') {
+                    self.at += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_space();
+                    if self.chars.get(self.at) != Some(&'"') {
+                        return Err("expected a key in quotes".to_string());
+                    }
+                    let key = self.string()?;
+                    self.skip_space();
+                    if self.next() != Some(':') {
+                        return Err("expected ':' after a key".to_string());
+                    }
+                    fields.push((key, self.value()?));
+                    self.skip_space();
+                    match self.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Json::Object(fields)),
+                        _ => return Err("expected ',' or '}' in an object".to_string()),
+                    }
+                }
+            }
+            _ => unreachable!("only called on an opening bracket"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.at += 1; // The opening quote
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None => return Err("unterminated string".to_string()),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| "bad \\u escape".to_string())?;
+                        s.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.at;
+        while self
+            .chars
+            .get(self.at)
+            .is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c))
+        {
+            self.at += 1;
+        }
+        let text: String = self.chars[start..self.at].iter().collect();
+        if text.chars().all(|c| c.is_ascii_digit()) {
+            if let Ok(n) = text.parse() {
+                return Ok(Json::Integer(n));
+            }
+        }
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("unexpected '{}'", self.chars[start]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_values_parse_up_to_the_limit() {
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(Json::parse(&nested).is_ok());
+    }
+
+    #[test]
+    fn deeper_nesting_is_an_error_not_a_crash() {
+        let deep = "[".repeat(200_000);
+        assert_eq!(Json::parse(&deep), Err("too deeply nested".to_string()));
+        let objects = "{\"a\":".repeat(MAX_DEPTH + 1);
+        assert_eq!(Json::parse(&objects), Err("too deeply nested".to_string()));
+    }
+
+    #[test]
+    fn whole_numbers_stay_exact() {
+        let seed = Json::parse("18446744073709551615").unwrap();
+        assert_eq!(seed, Json::Integer(u64::MAX));
+        assert_eq!(seed.as_u64(), Some(u64::MAX));
+        assert_eq!(seed.to_string(), "18446744073709551615");
+        let seed = Json::parse("9007199254740993").unwrap();
+        assert_eq!(seed.as_u64(), Some(9_007_199_254_740_993));
+    }
+
+    #[test]
+    fn rounded_numbers_are_not_seeds() {
+        assert_eq!(Json::parse("7.0").unwrap().as_u64(), Some(7));
+        assert_eq!(Json::parse("1e3").unwrap().as_u64(), Some(1000));
+        assert_eq!(Json::parse("9007199254740993.0").unwrap().as_u64(), None);
+        assert_eq!(Json::parse("1e19").unwrap().as_u64(), None);
+        assert_eq!(Json::parse("-1").unwrap().as_u64(), None);
+        assert_eq!(Json::parse("1.5").unwrap().as_u64(), None);
+        // Too big for a u64, it's an f64 like any other number
+        assert_eq!(Json::parse("18446744073709551616").unwrap().as_u64(), None);
+    }
+}
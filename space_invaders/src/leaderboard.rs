@@ -0,0 +1,206 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+
+// The best scores, kept separately for solo and co-op games in leaderboard.txt as
+// lines of '<category> <score>'. Solo scores from before the leaderboard, when the
+// game only kept high_score.txt, still count
+
+const FILE: &str = "leaderboard.txt";
+const OLD_HIGH_SCORE_FILE: &str = "high_score.txt";
+// Scores kept in each category
+pub const ENTRIES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+    Solo,
+    Coop,
+}
+
+impl Category {
+    const ALL: [Category; 2] = [Category::Solo, Category::Coop];
+
+    pub fn for_players(count: usize) -> Self {
+        if count > 1 {
+            Category::Coop
+        } else {
+            Category::Solo
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Solo => "solo",
+            Category::Coop => "co-op",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+    }
+}
+
+#[derive(Default)]
+pub struct Leaderboard {
+    scores: Vec<(Category, u32)>,
+}
+
+impl Leaderboard {
+    // Read the leaderboard, starting an empty one when there is none
+    pub fn load() -> Self {
+        let content = fs::read_to_string(FILE).unwrap_or_default();
+        let old_high_score = fs::read_to_string(OLD_HIGH_SCORE_FILE).ok();
+        Leaderboard::parse(&content, old_high_score.as_deref())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(FILE, self.format())
+    }
+
+    // A leaderboard from the contents of leaderboard.txt and high_score.txt. Lines
+    // that can't be read are skipped
+    fn parse(content: &str, old_high_score: Option<&str>) -> Self {
+        let mut leaderboard = Leaderboard::default();
+        for line in content.lines() {
+            if let Some((category, score)) = line.trim().split_once(' ') {
+                if let (Some(category), Ok(score)) =
+                    (Category::from_name(category), score.trim().parse())
+                {
+                    leaderboard.record(category, score);
+                }
+            }
+        }
+        if let Some(Ok(score)) = old_high_score.map(|content| content.trim().parse()) {
+            if !leaderboard.top(Category::Solo).contains(&score) {
+                leaderboard.record(Category::Solo, score);
+            }
+        }
+        leaderboard
+    }
+
+    fn format(&self) -> String {
+        self.scores
+            .iter()
+            .map(|(category, score)| format!("{} {}\n", category.name(), score))
+            .collect()
+    }
+
+    // The best scores in a category, highest first
+    pub fn top(&self, category: Category) -> Vec<u32> {
+        self.scores
+            .iter()
+            .filter(|entry| entry.0 == category)
+            .map(|entry| entry.1)
+            .collect()
+    }
+
+    pub fn best(&self, category: Category) -> u32 {
+        self.top(category).first().copied().unwrap_or(0)
+    }
+
+    // Add a score, keeping the best ENTRIES in its category. Returns its place on
+    // the board, counting from 1, if it made it
+    pub fn record(&mut self, category: Category, score: u32) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        let place = self.top(category).iter().filter(|&&s| s >= score).count();
+        if place >= ENTRIES {
+            return None;
+        }
+        self.scores.push((category, score));
+        self.scores.sort_by_key(|entry| Reverse(entry.1));
+        let mut kept = 0;
+        self.scores.retain(|entry| {
+            if entry.0 != category {
+                return true;
+            }
+            kept += 1;
+            kept <= ENTRIES
+        });
+        Some(place + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_are_placed_in_order() {
+        let mut leaderboard = Leaderboard::default();
+        assert_eq!(leaderboard.record(Category::Solo, 300), Some(1));
+        assert_eq!(leaderboard.record(Category::Solo, 500), Some(1));
+        assert_eq!(leaderboard.record(Category::Solo, 400), Some(2));
+        // A tie goes below the score it ties with
+        assert_eq!(leaderboard.record(Category::Solo, 400), Some(3));
+        assert_eq!(leaderboard.record(Category::Solo, 0), None);
+        assert_eq!(leaderboard.top(Category::Solo), [500, 400, 400, 300]);
+        assert_eq!(leaderboard.best(Category::Coop), 0);
+    }
+
+    #[test]
+    fn each_category_keeps_its_best_five() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 1..=5 {
+            leaderboard.record(Category::Coop, score * 100);
+        }
+        for score in 1..=7 {
+            leaderboard.record(Category::Solo, score * 10);
+        }
+        assert_eq!(leaderboard.top(Category::Solo), [70, 60, 50, 40, 30]);
+        assert_eq!(leaderboard.record(Category::Solo, 20), None);
+        assert_eq!(leaderboard.record(Category::Solo, 55), Some(3));
+        assert_eq!(leaderboard.top(Category::Solo), [70, 60, 55, 50, 40]);
+        // Solo scores never push co-op ones off the board
+        assert_eq!(leaderboard.top(Category::Coop), [500, 400, 300, 200, 100]);
+    }
+
+    #[test]
+    fn the_board_survives_a_round_trip() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.record(Category::Solo, 120);
+        leaderboard.record(Category::Coop, 900);
+        leaderboard.record(Category::Solo, 80);
+        let text = leaderboard.format();
+        assert_eq!(text, "co-op 900\nsolo 120\nsolo 80\n");
+        let read = Leaderboard::parse(&text, None);
+        assert_eq!(read.top(Category::Solo), [120, 80]);
+        assert_eq!(read.top(Category::Coop), [900]);
+    }
+
+    #[test]
+    fn corrupt_lines_are_skipped() {
+        let text =
+            "solo 100\nsolo\nduo 50\nsolo -5\nco-op 2x\n\n  co-op 70  \nsolo 999999999999\nsolo 40";
+        let leaderboard = Leaderboard::parse(text, None);
+        assert_eq!(leaderboard.top(Category::Solo), [100, 40]);
+        assert_eq!(leaderboard.top(Category::Coop), [70]);
+        // More than five lines in a category are trimmed as they are read
+        let long: String = (1..=8).map(|score| format!("solo {}\n", score)).collect();
+        let leaderboard = Leaderboard::parse(&long, None);
+        assert_eq!(leaderboard.top(Category::Solo), [8, 7, 6, 5, 4]);
+    }
+
+    #[test]
+    fn the_old_high_score_is_imported_once() {
+        let leaderboard = Leaderboard::parse("", Some("1234\n"));
+        assert_eq!(leaderboard.top(Category::Solo), [1234]);
+        assert!(leaderboard.top(Category::Coop).is_empty());
+        // Saved to the leaderboard already, it isn't counted twice
+        let leaderboard = Leaderboard::parse("solo 1234\nsolo 50\n", Some("1234"));
+        assert_eq!(leaderboard.top(Category::Solo), [1234, 50]);
+        // A full board only takes it if it is good enough
+        let full = "solo 50\nsolo 40\nsolo 30\nsolo 20\nsolo 10\n";
+        let leaderboard = Leaderboard::parse(full, Some("35"));
+        assert_eq!(leaderboard.top(Category::Solo), [50, 40, 35, 30, 20]);
+        let leaderboard = Leaderboard::parse(full, Some("5"));
+        assert_eq!(leaderboard.top(Category::Solo), [50, 40, 30, 20, 10]);
+        for unreadable in ["", "lots", "-3"] {
+            let leaderboard = Leaderboard::parse("", Some(unreadable));
+            assert!(leaderboard.top(Category::Solo).is_empty());
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use rodio::Sink;
+use std::io::{self, Write};
+use std::sync::mpsc;
+use termion::color;
+use termion::event::Key;
+use termion::screen::AlternateScreen;
+use termion::style;
+
+use crate::difficulty::Difficulty;
+use crate::enemies;
+use crate::game_mode::GameMode;
+use crate::levels::{self, Wave, WaveEvent, MAX_SPEED, MIN_SPEED};
+use crate::patterns::Patterns;
+use crate::powerups::Powerup;
+use crate::{run_game, Game, GameExit, HEIGHT, WIDTH};
+
+// Screen row of the top border, the playfield starts on the row below
+const FIELD_TOP: u16 = 3;
+// Screen column of the help text
+const HELP_COLUMN: u16 = WIDTH as u16 + 5;
+
+// How previews are played, the same as a game started from the menu
+pub struct Preview<'a> {
+    pub patterns: &'a Patterns,
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+}
+
+// Editor state for one level file
+struct Editor {
+    waves: Vec<Wave>,
+    current: usize,
+    cursor_x: usize,
+    cursor_y: usize,
+    naming: bool,
+    dirty: bool,
+    confirm_quit: bool,
+    message: String,
+}
+
+impl Editor {
+    fn wave(&mut self) -> &mut Wave {
+        &mut self.waves[self.current]
+    }
+
+    // Put an enemy under the cursor, replacing whatever was there
+    fn place(&mut self, glyph: char) {
+        let (x, y) = (self.cursor_x, self.cursor_y);
+        let wave = self.wave();
+        wave.enemies.retain(|e| (e.0, e.1) != (x, y));
+        wave.enemies.push((x, y, glyph));
+        self.dirty = true;
+    }
+
+    // Delete the enemy under the cursor
+    fn remove(&mut self) {
+        let (x, y) = (self.cursor_x, self.cursor_y);
+        let wave = self.wave();
+        let before = wave.enemies.len();
+        wave.enemies.retain(|e| (e.0, e.1) != (x, y));
+        if wave.enemies.len() != before {
+            self.dirty = true;
+        }
+    }
+
+    // Change the wave speed, starting from the speed the game would pick for this level
+    fn adjust_speed(&mut self, delta: isize) {
+        let level = self.current + 1;
+        let wave = self.wave();
+        let speed = wave.speed.unwrap_or(20 - level.min(15)) as isize + delta;
+        wave.speed = Some((speed.max(MIN_SPEED as isize) as usize).min(MAX_SPEED));
+        self.dirty = true;
+    }
+
+    fn toggle_health_event(&mut self) {
+        let wave = self.wave();
+        if wave.events.contains(&WaveEvent::HealthEnemy) {
+            wave.events.retain(|e| *e != WaveEvent::HealthEnemy);
+        } else {
+            wave.events.push(WaveEvent::HealthEnemy);
+        }
+        self.dirty = true;
+    }
+
+    // Cycle the wave's powerup drop through none and each powerup type
+    fn cycle_powerup_event(&mut self) {
+        let wave = self.wave();
+        let current = wave.events.iter().find_map(|e| match e {
+            WaveEvent::Powerup(powerup) => Some(*powerup),
+            _ => None,
+        });
+        wave.events.retain(|e| !matches!(e, WaveEvent::Powerup(_)));
+        let next = match current {
+            None => Some(Powerup::ALL[0]),
+            Some(powerup) => Powerup::ALL
+                .iter()
+                .position(|&p| p == powerup)
+                .and_then(|i| Powerup::ALL.get(i + 1))
+                .copied(),
+        };
+        if let Some(powerup) = next {
+            wave.events.push(WaveEvent::Powerup(powerup));
+        }
+        self.dirty = true;
+    }
+
+    // Check every wave, moving to the first broken one
+    fn validate(&mut self) -> bool {
+        for (i, wave) in self.waves.iter().enumerate() {
+            if let Err(problem) = levels::validate_wave(wave) {
+                self.current = i;
+                self.message = format!("Wave {}: {}", i + 1, problem);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Edit a level file. Saved waves are written back to `waves` for the next game.
+// Returns whether the player quit the whole game from a preview
+pub fn run_level_editor(
+    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
+    rx: &mpsc::Receiver<Key>,
+    laser_sink: &Sink,
+    path: &str,
+    waves: &mut Vec<Wave>,
+    preview: &Preview,
+) -> io::Result<bool> {
+    let mut editor = Editor {
+        waves: if waves.is_empty() {
+            vec![Wave::default()]
+        } else {
+            waves.clone()
+        },
+        current: 0,
+        cursor_x: WIDTH / 2,
+        cursor_y: 3,
+        naming: false,
+        dirty: false,
+        confirm_quit: false,
+        message: String::new(),
+    };
+
+    loop {
+        draw_editor(screen, &editor, path)?;
+        let key = match rx.recv() {
+            Ok(key) => key,
+            Err(_) => return Ok(true),
+        };
+
+        // Typing a wave name takes over the keyboard until Enter or Esc
+        if editor.naming {
+            match key {
+                Key::Char('\n') | Key::Esc => editor.naming = false,
+                Key::Backspace => {
+                    editor.wave().name.pop();
+                    editor.dirty = true;
+                }
+                Key::Char(c) if !c.is_control() => {
+                    editor.wave().name.push(c);
+                    editor.dirty = true;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let confirm_quit = editor.confirm_quit;
+        editor.confirm_quit = false;
+        editor.message.clear();
+        match key {
+            Key::Up => editor.cursor_y = editor.cursor_y.saturating_sub(1),
+            Key::Down => editor.cursor_y = (editor.cursor_y + 1).min(HEIGHT - 2),
+            Key::Left => editor.cursor_x = editor.cursor_x.saturating_sub(1),
+            Key::Right => editor.cursor_x = (editor.cursor_x + 1).min(WIDTH - 1),
+            Key::Char(c) if enemies::kind_for(c).is_some() => editor.place(c),
+            Key::Char('x') | Key::Char('X') | Key::Backspace | Key::Delete => editor.remove(),
+            Key::Char('+') => editor.adjust_speed(1),
+            Key::Char('-') => editor.adjust_speed(-1),
+            Key::Char('0') => {
+                editor.wave().speed = None;
+                editor.dirty = true;
+            }
+            Key::Char('b') => {
+                let wave = editor.wave();
+                wave.boss = !wave.boss;
+                editor.dirty = true;
+            }
+            Key::Char('h') => editor.toggle_health_event(),
+            Key::Char('p') => editor.cycle_powerup_event(),
+            Key::Char('e') => editor.naming = true,
+            Key::Char('[') => editor.current = editor.current.saturating_sub(1),
+            Key::Char(']') => editor.current = (editor.current + 1).min(editor.waves.len() - 1),
+            Key::Char('n') => {
+                editor.current += 1;
+                editor.waves.insert(editor.current, Wave::default());
+                editor.dirty = true;
+            }
+            Key::Char('k') => {
+                if editor.waves.len() > 1 {
+                    editor.waves.remove(editor.current);
+                    editor.current = editor.current.min(editor.waves.len() - 1);
+                } else {
+                    editor.waves[0] = Wave::default();
+                }
+                editor.dirty = true;
+            }
+            Key::Char('\n') => {
+                if let Err(problem) = levels::validate_wave(&editor.waves[editor.current]) {
+                    editor.message = format!("Can't play: {}", problem);
+                    continue;
+                }
+                // Play this wave at its real level, with the rest of the file around it
+                let mut game = Game::new();
+                game.waves = editor.waves.clone();
+                game.patterns = preview.patterns.clone();
+                game.mode = preview.mode;
+                game.difficulty = preview.difficulty;
+                game.level = editor.current + 1;
+                game.start_level();
+                editor.message =
+                    match run_game(screen, rx, Some(laser_sink), &mut game, true, None, None)? {
+                        GameExit::Cleared => "Wave cleared!".to_string(),
+                        GameExit::GameOver => "Game over during preview".to_string(),
+                        GameExit::Stopped => "Preview stopped".to_string(),
+                        GameExit::Quit => return Ok(true),
+                    };
+            }
+            Key::Char('s') => {
+                if !editor.validate() {
+                    continue;
+                }
+                match levels::save_levels(path, &editor.waves) {
+                    Ok(()) => {
+                        *waves = editor.waves.clone();
+                        editor.dirty = false;
+                        editor.message = format!("Saved {} waves to {}", waves.len(), path);
+                    }
+                    Err(e) => editor.message = format!("Could not save {}: {}", path, e),
+                }
+            }
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') if !editor.dirty || confirm_quit => {
+                return Ok(false)
+            }
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') => {
+                editor.confirm_quit = true;
+                editor.message = "Unsaved changes, press Q again to discard them".to_string();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Draw the wave being edited, its properties and the key help
+fn draw_editor(
+    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
+    editor: &Editor,
+    path: &str,
+) -> io::Result<()> {
+    let wave = &editor.waves[editor.current];
+    write!(screen, "{}", termion::clear::All)?;
+
+    write!(
+        screen,
+        "{}{}{}LEVEL EDITOR{}{} {}{} | {}Wave {}/{}{}{}",
+        termion::cursor::Goto(1, 1),
+        style::Bold,
+        color::Fg(color::Cyan),
+        style::Reset,
+        color::Fg(color::Reset),
+        path,
+        if editor.dirty { " *" } else { "" },
+        color::Fg(color::Yellow),
+        editor.current + 1,
+        editor.waves.len(),
+        if wave.name.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", wave.name)
+        },
+        if editor.naming { "_" } else { "" },
+    )?;
+
+    let speed = match wave.speed {
+        Some(speed) => speed.to_string(),
+        None => "default".to_string(),
+    };
+    let powerup = wave
+        .events
+        .iter()
+        .find_map(|e| match e {
+            WaveEvent::Powerup(powerup) => Some(powerup.name().to_string()),
+            _ => None,
+        })
+        .unwrap_or_else(|| "none".to_string());
+    write!(
+        screen,
+        "{}{}Speed: {} | Boss: {} | Health drop: {} | Powerup drop: {} | Enemies: {}",
+        termion::cursor::Goto(1, 2),
+        color::Fg(color::Reset),
+        speed,
+        if wave.boss { "yes" } else { "no" },
+        if wave.events.contains(&WaveEvent::HealthEnemy) {
+            "yes"
+        } else {
+            "no"
+        },
+        powerup,
+        wave.enemies.len()
+    )?;
+
+    // Playfield with a border, the player row is shown but can't be edited
+    let border = format!("+{}+", "-".repeat(WIDTH));
+    write!(screen, "{}{}", termion::cursor::Goto(1, FIELD_TOP), border)?;
+    for y in 0..HEIGHT {
+        let mut line = String::from("|");
+        for x in 0..WIDTH {
+            let (cell, cell_color): (char, &dyn color::Color) = if y == HEIGHT - 1 {
+                if x == WIDTH / 2 {
+                    ('A', &color::Blue)
+                } else {
+                    (' ', &color::Reset)
+                }
+            } else {
+                match wave.enemies.iter().find(|e| (e.0, e.1) == (x, y)) {
+                    Some(&(_, _, glyph)) => match enemies::kind_for(glyph) {
+                        Some(kind) => (glyph, kind.color()),
+                        None => (glyph, &color::Reset),
+                    },
+                    None => ('.', &color::LightBlack),
+                }
+            };
+            line.push_str(&format!("{}", color::Fg(cell_color)));
+            if (x, y) == (editor.cursor_x, editor.cursor_y) {
+                line.push_str(&format!("{}{}{}", style::Invert, cell, style::NoInvert));
+            } else {
+                line.push(cell);
+            }
+        }
+        line.push_str(&format!("{}|", color::Fg(color::Reset)));
+        write!(
+            screen,
+            "{}{}",
+            termion::cursor::Goto(1, FIELD_TOP + 1 + y as u16),
+            line
+        )?;
+    }
+    write!(
+        screen,
+        "{}{}",
+        termion::cursor::Goto(1, FIELD_TOP + 1 + HEIGHT as u16),
+        border
+    )?;
+
+    let glyphs: Vec<String> = enemies::ALL_KINDS
+        .iter()
+        .map(|kind| kind.glyph().to_string())
+        .collect();
+    let place_help = format!("{:<12}place enemy", glyphs.join(""));
+    let help = [
+        "Arrows      move cursor",
+        place_help.as_str(),
+        "X / Del     delete enemy",
+        "",
+        "- / +       wave speed",
+        "0           default speed",
+        "b           toggle boss",
+        "h           toggle health drop",
+        "p           cycle powerup drop",
+        "e           rename wave",
+        "",
+        "[ / ]       previous/next wave",
+        "n           new wave",
+        "k           delete wave",
+        "",
+        "Enter       play this wave",
+        "Esc         stop playing",
+        "s           save",
+        "q           back",
+    ];
+    for (i, line) in help.iter().enumerate() {
+        write!(
+            screen,
+            "{}{}{}",
+            termion::cursor::Goto(HELP_COLUMN, FIELD_TOP + 1 + i as u16),
+            color::Fg(color::LightBlue),
+            line
+        )?;
+    }
+    write!(
+        screen,
+        "{}{}{}{}",
+        termion::cursor::Goto(HELP_COLUMN, FIELD_TOP + 3 + help.len() as u16),
+        color::Fg(color::Green),
+        editor.message,
+        color::Fg(color::Reset)
+    )?;
+
+    screen.flush()?;
+    Ok(())
+}
@@ -0,0 +1,399 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::bunkers::{BunkerLayout, BUNKER_WIDTH, MAX_BUNKERS};
+use crate::enemies;
+use crate::powerups::Powerup;
+use crate::{HEIGHT, WIDTH};
+
+// Header written at the top of saved level files
+const FILE_HEADER: &str = "\
+# Space Invaders level file
+#
+# Each wave is one level. Once the waves run out the game falls back to
+# random formations, with a boss every fifth level.
+#
+#   wave <name>           start a new wave (the name is optional)
+#   speed <1-20>          ticks between enemy steps, lower is faster
+#   boss                  spawn the boss when this wave starts
+#   event health          drop a health enemy when the wave starts
+#   event powerup <P>     drop powerup P when the wave starts (B M S R $ ~ @ ♥)
+#   bunkers <0-7>         spread this many bunkers evenly above the player (4 if unset)
+#   bunker <x>            put a bunker's left edge at column x, once per bunker
+#   row <y> <cells>       formation row y, one cell per column, '.' is empty
+";
+// Allowed range for a wave's enemy speed (ticks between enemy steps)
+pub const MIN_SPEED: usize = 1;
+pub const MAX_SPEED: usize = 20;
+
+// Something that happens when a wave starts, besides spawning its formation
+#[derive(Clone, Debug, PartialEq)]
+pub enum WaveEvent {
+    HealthEnemy,
+    Powerup(Powerup),
+}
+
+// One hand-authored wave
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Wave {
+    pub name: String,
+    pub enemies: Vec<(usize, usize, char)>, // (x, y, glyph)
+    pub speed: Option<usize>,
+    pub boss: bool,
+    pub events: Vec<WaveEvent>,
+    pub bunkers: Option<BunkerLayout>, // None for the default layout
+}
+
+// A problem found while loading a level file
+#[derive(Debug)]
+pub struct LevelError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> LevelError {
+    LevelError {
+        line,
+        message: message.into(),
+    }
+}
+
+// Load and validate the waves in a level file
+pub fn load_levels(path: &str) -> Result<Vec<Wave>, LevelError> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| error(0, format!("could not read {}: {}", path, e)))?;
+    parse_levels(&source)
+}
+
+// Write waves back out in the level file format
+pub fn save_levels(path: &str, waves: &[Wave]) -> io::Result<()> {
+    fs::write(path, format_levels(waves))
+}
+
+// Format waves as a level file that parse_levels reads back unchanged
+pub fn format_levels(waves: &[Wave]) -> String {
+    let mut output = String::from(FILE_HEADER);
+    for wave in waves {
+        output.push('\n');
+        if wave.name.is_empty() {
+            output.push_str("wave\n");
+        } else {
+            output.push_str(&format!("wave {}\n", wave.name));
+        }
+        if let Some(speed) = wave.speed {
+            output.push_str(&format!("speed {}\n", speed));
+        }
+        if wave.boss {
+            output.push_str("boss\n");
+        }
+        match &wave.bunkers {
+            Some(BunkerLayout::Evenly(count)) => output.push_str(&format!("bunkers {}\n", count)),
+            Some(BunkerLayout::At(columns)) => {
+                for x in columns {
+                    output.push_str(&format!("bunker {}\n", x));
+                }
+            }
+            None => {}
+        }
+        for event in &wave.events {
+            match event {
+                WaveEvent::HealthEnemy => output.push_str("event health\n"),
+                WaveEvent::Powerup(powerup) => {
+                    output.push_str(&format!("event powerup {}\n", powerup.glyph()))
+                }
+            }
+        }
+        let mut rows: Vec<usize> = wave.enemies.iter().map(|&(_, y, _)| y).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        for y in rows {
+            let mut cells = vec!['.'; WIDTH];
+            for &(x, _, enemy_type) in wave.enemies.iter().filter(|e| e.1 == y) {
+                cells[x] = enemy_type;
+            }
+            let cells: String = cells.into_iter().collect();
+            output.push_str(&format!("row {} {}\n", y, cells.trim_end_matches('.')));
+        }
+    }
+    output
+}
+
+// Parse a level file. The format is line based and described in FILE_HEADER,
+// lines starting with '#' are comments
+pub fn parse_levels(source: &str) -> Result<Vec<Wave>, LevelError> {
+    let mut waves: Vec<Wave> = Vec::new();
+    let mut wave_line = 0;
+    let mut rows_seen: Vec<usize> = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (directive, rest) = match line.split_once(char::is_whitespace) {
+            Some((directive, rest)) => (directive, rest.trim()),
+            None => (line, ""),
+        };
+
+        if directive == "wave" {
+            if let Some(wave) = waves.last() {
+                check_wave(wave, wave_line)?;
+            }
+            waves.push(Wave {
+                name: rest.to_string(),
+                ..Wave::default()
+            });
+            wave_line = line_number;
+            rows_seen.clear();
+            continue;
+        }
+
+        let wave = match waves.last_mut() {
+            Some(wave) => wave,
+            None => {
+                return Err(error(
+                    line_number,
+                    format!("'{}' must come after a 'wave' line", directive),
+                ))
+            }
+        };
+
+        match directive {
+            "speed" => {
+                if wave.speed.is_some() {
+                    return Err(error(line_number, "speed is already set for this wave"));
+                }
+                let speed: usize = rest
+                    .parse()
+                    .map_err(|_| error(line_number, format!("invalid speed '{}'", rest)))?;
+                if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+                    return Err(error(
+                        line_number,
+                        format!("speed must be between {} and {}", MIN_SPEED, MAX_SPEED),
+                    ));
+                }
+                wave.speed = Some(speed);
+            }
+            "boss" => {
+                if !rest.is_empty() {
+                    return Err(error(line_number, "'boss' takes no arguments"));
+                }
+                if wave.boss {
+                    return Err(error(line_number, "wave is already a boss wave"));
+                }
+                wave.boss = true;
+            }
+            "event" => wave.events.push(parse_event(rest, line_number)?),
+            "bunkers" => {
+                if wave.bunkers.is_some() {
+                    return Err(error(line_number, "bunkers are already set for this wave"));
+                }
+                let count: usize = rest
+                    .parse()
+                    .map_err(|_| error(line_number, format!("invalid bunker count '{}'", rest)))?;
+                if count > MAX_BUNKERS {
+                    return Err(error(
+                        line_number,
+                        format!("at most {} bunkers fit on the playfield", MAX_BUNKERS),
+                    ));
+                }
+                wave.bunkers = Some(BunkerLayout::Evenly(count));
+            }
+            "bunker" => {
+                let x: usize = rest
+                    .parse()
+                    .map_err(|_| error(line_number, format!("invalid column '{}'", rest)))?;
+                if x + BUNKER_WIDTH > WIDTH {
+                    return Err(error(
+                        line_number,
+                        format!(
+                            "bunker column must be between 0 and {}",
+                            WIDTH - BUNKER_WIDTH
+                        ),
+                    ));
+                }
+                match &mut wave.bunkers {
+                    None => wave.bunkers = Some(BunkerLayout::At(vec![x])),
+                    Some(BunkerLayout::At(columns)) => columns.push(x),
+                    Some(BunkerLayout::Evenly(_)) => {
+                        return Err(error(
+                            line_number,
+                            "'bunker' can't be mixed with 'bunkers' in one wave",
+                        ))
+                    }
+                }
+            }
+            "row" => {
+                let (y, cells) = match rest.split_once(char::is_whitespace) {
+                    Some((y, cells)) => (y, cells.trim()),
+                    None => return Err(error(line_number, "expected 'row <y> <cells>'")),
+                };
+                let y: usize = y
+                    .parse()
+                    .map_err(|_| error(line_number, format!("invalid row number '{}'", y)))?;
+                if y >= HEIGHT - 1 {
+                    return Err(error(
+                        line_number,
+                        format!("row must be between 0 and {}", HEIGHT - 2),
+                    ));
+                }
+                if rows_seen.contains(&y) {
+                    return Err(error(line_number, format!("row {} is defined twice", y)));
+                }
+                rows_seen.push(y);
+                if cells.chars().count() > WIDTH {
+                    return Err(error(
+                        line_number,
+                        format!("row is wider than the playfield ({} columns)", WIDTH),
+                    ));
+                }
+                for (x, cell) in cells.chars().enumerate() {
+                    if cell == '.' {
+                        continue;
+                    }
+                    if enemies::kind_for(cell).is_none() {
+                        return Err(error(
+                            line_number,
+                            format!("unknown enemy type '{}' in column {}", cell, x),
+                        ));
+                    }
+                    wave.enemies.push((x, y, cell));
+                }
+            }
+            _ => {
+                return Err(error(
+                    line_number,
+                    format!("unknown directive '{}'", directive),
+                ))
+            }
+        }
+    }
+
+    if let Some(wave) = waves.last() {
+        check_wave(wave, wave_line)?;
+    }
+    Ok(waves)
+}
+
+fn parse_event(rest: &str, line_number: usize) -> Result<WaveEvent, LevelError> {
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    match words.as_slice() {
+        ["health"] => Ok(WaveEvent::HealthEnemy),
+        ["powerup", glyph] => {
+            let mut chars = glyph.chars();
+            match (chars.next().and_then(Powerup::from_glyph), chars.next()) {
+                (Some(powerup), None) => Ok(WaveEvent::Powerup(powerup)),
+                _ => Err(error(line_number, format!("unknown powerup '{}'", glyph))),
+            }
+        }
+        _ => Err(error(line_number, format!("unknown event '{}'", rest))),
+    }
+}
+
+// A wave must give the player something to fight
+pub fn validate_wave(wave: &Wave) -> Result<(), &'static str> {
+    if wave.enemies.is_empty() && !wave.boss {
+        return Err("wave has no enemies and is not a boss wave");
+    }
+    Ok(())
+}
+
+fn check_wave(wave: &Wave, line_number: usize) -> Result<(), LevelError> {
+    validate_wave(wave).map_err(|message| error(line_number, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+wave Opening
+speed 8
+boss
+bunker 3
+bunker 20
+event health
+event powerup R
+row 2 N..Z
+row 4 .S.T
+
+wave
+bunkers 0
+row 1 H
+";
+
+    #[test]
+    fn waves_survive_a_round_trip() {
+        let waves = parse_levels(SOURCE).unwrap();
+        assert_eq!(waves.len(), 2);
+        let first = &waves[0];
+        assert_eq!(first.name, "Opening");
+        assert_eq!(first.speed, Some(8));
+        assert!(first.boss);
+        assert_eq!(first.bunkers, Some(BunkerLayout::At(vec![3, 20])));
+        assert_eq!(
+            first.events,
+            [
+                WaveEvent::HealthEnemy,
+                WaveEvent::Powerup(Powerup::RapidFire)
+            ]
+        );
+        assert_eq!(
+            first.enemies,
+            [(0, 2, 'N'), (3, 2, 'Z'), (1, 4, 'S'), (3, 4, 'T')]
+        );
+        assert_eq!(waves[1].bunkers, Some(BunkerLayout::Evenly(0)));
+
+        let saved = format_levels(&waves);
+        assert_eq!(parse_levels(&saved).unwrap(), waves);
+        assert_eq!(format_levels(&parse_levels(&saved).unwrap()), saved);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let cases = [
+            ("speed 5", "line 1: 'speed' must come after a 'wave' line"),
+            (
+                "wave\n\nrow 3 N.X",
+                "line 3: unknown enemy type 'X' in column 2",
+            ),
+            (
+                "wave\nspeed 30\nrow 1 N",
+                "line 2: speed must be between 1 and 20",
+            ),
+            ("wave\nrow 1 N\nrow 1 Z", "line 3: row 1 is defined twice"),
+            (
+                "wave\nbunkers 2\nbunker 4\nrow 1 N",
+                "line 3: 'bunker' can't be mixed with 'bunkers' in one wave",
+            ),
+            (
+                "wave\nbunker 58",
+                "line 2: bunker column must be between 0 and 53",
+            ),
+            ("wave\nevent powerup Q", "line 2: unknown powerup 'Q'"),
+            (
+                "wave a\nrow 2 N\n# b is empty\nwave b",
+                "line 4: wave has no enemies and is not a boss wave",
+            ),
+        ];
+        for (source, expected) in cases {
+            match parse_levels(source) {
+                Ok(_) => panic!("{:?} should not parse", source),
+                Err(e) => assert_eq!(e.to_string(), expected, "for {:?}", source),
+            }
+        }
+    }
+}
@@ -1,9 +1,9 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rodio::source::{SineWave, Source};
 use rodio::{Decoder, OutputStream, Sink};
 use std::env;
 use std::fs::File;
-use std::fs::OpenOptions;
-use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::{self, stdout, Write};
 use std::path::Path;
@@ -16,166 +16,361 @@ use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 
+mod agent;
+mod attract;
+mod boss;
+mod bosses;
+mod bullets;
+mod bunkers;
+mod collision;
+mod debug_overlay;
+mod difficulty;
 mod display_game_over_screen;
 mod display_option_screen;
 mod display_pause_screen;
 mod display_start_screen;
 mod display_tutorial_screen;
-
+mod enemies;
+mod events;
+mod formation;
+mod fuzz;
+mod game_mode;
+mod json;
+mod leaderboard;
+mod level_editor;
+mod levels;
+mod netplay;
+mod patterns;
+mod player;
+mod powerups;
+mod serve;
+mod simulate;
+mod snapshot;
+mod spectate;
+mod stdio_env;
+mod ufo;
+mod world;
+
+use crate::agent::{Agent, Autopilot};
+use crate::boss::{Boss, BossHit, PartKind};
+use crate::bullets::Bullet;
+use crate::bunkers::Bunkers;
+use crate::debug_overlay::DebugOverlay;
+use crate::difficulty::Difficulty;
 use crate::display_game_over_screen::display_game_over_screen;
 use crate::display_option_screen::display_option_screen;
 use crate::display_pause_screen::display_pause_screen;
 use crate::display_start_screen::display_start_screen;
 use crate::display_tutorial_screen::display_tutorial_screen;
+use crate::enemies::EnemyKind;
+use crate::events::{Cause, GameEvent, Stats};
+use crate::game_mode::GameMode;
+use crate::leaderboard::{Category, Leaderboard};
+use crate::level_editor::{run_level_editor, Preview};
+use crate::levels::{Wave, WaveEvent};
+use crate::patterns::Patterns;
+use crate::player::{Action, Player};
+use crate::powerups::Powerup;
+use crate::spectate::{Endpoint, Spectators};
+use crate::ufo::Ufo;
+use crate::world::PLAYER_ROW;
 
 // Define game constants
 const WIDTH: usize = 60;
 const HEIGHT: usize = 30;
 const LASER_HITBOX_WIDTH: usize = 3;
 const IMMUNITY_DURATION: Duration = Duration::from_secs(1);
+const DEFAULT_LEVEL_FILE: &str = "levels.txt";
+const DEFAULT_PATTERN_FILE: &str = "patterns.txt";
+const TICK_DURATION: Duration = Duration::from_millis(50);
+const POWERUP_DURATION_TICKS: u8 = 100;
+const POWERUP_INTERVAL: Duration = Duration::from_secs(30);
+const HEALTH_ENEMY_INTERVAL: Duration = Duration::from_secs(60);
+// Ticks between shots from a classic formation
+const FORMATION_FIRE_INTERVAL: u8 = 30;
+// The UFO crosses at a random time between these after the last one
+const UFO_MIN_INTERVAL: Duration = Duration::from_secs(20);
+const UFO_MAX_INTERVAL: Duration = Duration::from_secs(40);
+// Ticks a score popup stays up, rising a row every POPUP_RISE_TICKS
+const POPUP_TICKS: u8 = 24;
+const POPUP_RISE_TICKS: u8 = 8;
+
+// Number of game ticks in a duration. Game timers count ticks rather than wall
+// clock time, so a seeded game plays out the same way every time
+const fn ticks(duration: Duration) -> u64 {
+    duration.as_millis() as u64 / TICK_DURATION.as_millis() as u64
+}
+
+// A cell of the playfield as drawn: its glyph and color
+type ScreenCell = (char, &'static dyn color::Color);
+
+// How a call to run_game ended
+enum GameExit {
+    GameOver,
+    Cleared, // Preview games end once their wave is beaten
+    Stopped, // Preview games can be stopped with Esc
+    Quit,
+}
 
 // Game struct to hold all game state
 struct Game {
-    player: usize,
+    players: Vec<Player>, // One ship each, two in co-op
     enemies: Vec<Enemy>,
-    bullets: Vec<(usize, usize, bool)>, // (x, y, is_enemy_bullet)
-    powerups: Vec<(usize, usize, char)>,
-    explosions: Vec<(usize, usize, u8)>,
-    score: u32,
+    bullets: Vec<Bullet>,
+    pending_bullets: Vec<(u64, Bullet)>, // Held back by a pattern, with the tick they fire on
+    powerups: Vec<(i32, i32, Powerup)>,
+    explosions: Vec<(i32, i32, u8)>,
+    popups: Vec<(i32, i32, u32, u8)>, // Points scored, floating up from where they were won
     high_score: u32,
     level: usize,
-    lives: usize,
     enemy_move_counter: usize,
-    powerup_active: Option<char>,
-    powerup_timer: u8,
+    mode: GameMode,
+    difficulty: Difficulty,
+    formation_direction: i32, // Classic mode: which way the formation is marching
+    formation_size: usize,    // Classic mode: enemies in the wave when it spawned
+    formation_shoot_timer: u8,
+    versus: bool, // Players race for points and the game ends when either is out
     start_time: Instant,
-    last_powerup_time: Instant,
-    last_health_enemy_time: Instant,
+    tick: u64, // Ticks played so far, drives every game timer
+    last_powerup_tick: u64,
+    last_health_enemy_tick: u64,
     powerup_move_counter: usize,
     paused: bool,
     boss: Option<Boss>,
-    last_hit_time: Option<Instant>,
+    bunkers: Bunkers,
+    ufo: Option<Ufo>,
+    next_ufo_tick: u64,
+    waves: Vec<Wave>,
+    patterns: Patterns,
+    enemy_speed: Option<usize>,
+    events: Vec<GameEvent>, // Drained by the game loop every tick
+    stats: Stats,
+    rng: StdRng, // All game randomness comes from here, so a seed replays a game
+    seed: u64,   // What `rng` was seeded with
 }
-#[allow(dead_code)]
+// Positions are in world coordinates, see world.rs
 struct Enemy {
-    x: usize,
-    y: usize,
-    enemy_type: char,
-    color: usize,
+    x: i32,
+    y: i32,
+    // Where the enemy was at the last collision check, it swept everything in between
+    last_position: (i32, i32),
+    kind: &'static dyn EnemyKind,
     health: u8,
     shoot_timer: u8,
+    // Classic mode: whether the enemy is part of the marching block. Health enemies
+    // dropping in at a random column aren't, they only tag along
+    in_formation: bool,
 }
 
-struct Boss {
-    x: usize,
-    y: usize,
-    health: u16,
-    max_health: u16,
-    phase: u8,
-    shoot_timer: u8,
-    direction: i8,
-    move_timer: u32,
+impl Enemy {
+    // Create an enemy with the starting health for its kind
+    fn new(x: i32, y: i32, kind: &'static dyn EnemyKind, rng: &mut impl Rng) -> Self {
+        Enemy {
+            x,
+            y,
+            last_position: (x, y),
+            kind,
+            health: kind.spawn_health(),
+            shoot_timer: match kind.fire_interval() {
+                Some(interval) => rng.gen_range(0..interval),
+                None => 0,
+            },
+            in_formation: true,
+        }
+    }
+
+    // A health enemy dropping in at a random column along the top
+    fn drop_in(rng: &mut impl Rng) -> Self {
+        Enemy {
+            in_formation: false,
+            ..Enemy::new(rng.gen_range(0..WIDTH as i32), 0, &enemies::Health, rng)
+        }
+    }
 }
 
 impl Game {
     // Initialize a new game
     fn new() -> Self {
-        let high_score = Game::load_high_score();
-        Game {
-            player: WIDTH / 2,
+        Game::with_seed(rand::random())
+    }
+
+    // Initialize a game whose randomness comes from a seed
+    fn with_seed(seed: u64) -> Self {
+        let mut game = Game {
+            players: Player::spawn(1),
             enemies: Vec::new(),
             bullets: Vec::new(),
+            pending_bullets: Vec::new(),
             powerups: Vec::new(),
             explosions: Vec::new(),
-            score: 0,
-            high_score,
+            popups: Vec::new(),
+            high_score: Leaderboard::load().best(Category::Solo),
             level: 1,
-            lives: 3,
             enemy_move_counter: 0,
-            powerup_active: None,
-            powerup_timer: 0,
+            mode: GameMode::Arcade,
+            difficulty: Difficulty::Normal,
+            formation_direction: 1,
+            formation_size: 0,
+            formation_shoot_timer: 0,
+            versus: false,
             start_time: Instant::now(),
-            last_powerup_time: Instant::now(),
-            last_health_enemy_time: Instant::now(),
+            tick: 0,
+            last_powerup_tick: 0,
+            last_health_enemy_tick: 0,
             powerup_move_counter: 0,
             paused: false,
             boss: None,
-            last_hit_time: None,
+            bunkers: Bunkers::default(),
+            ufo: None,
+            next_ufo_tick: 0,
+            waves: Vec::new(),
+            patterns: Patterns::bundled(),
+            enemy_speed: None,
+            events: Vec::new(),
+            stats: Stats::default(),
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+        };
+        game.schedule_ufo();
+        game
+    }
+    // Play with this many ships, against the high score for solo or co-op games
+    fn set_player_count(&mut self, count: usize) {
+        self.players = Player::spawn(count);
+        self.high_score = Leaderboard::load().best(self.category());
+    }
+
+    // Which leaderboard the game's score goes on
+    fn category(&self) -> Category {
+        Category::for_players(self.players.len())
+    }
+
+    // The team's score, every player's points together
+    fn score(&self) -> u32 {
+        self.players.iter().map(|player| player.score).sum()
+    }
+
+    // Put the final score on the leaderboard. Returns the updated board and the
+    // score's place on it, if it made it
+    fn save_score(&self) -> (Leaderboard, Option<usize>) {
+        let mut leaderboard = Leaderboard::load();
+        let place = leaderboard.record(self.category(), self.score());
+        let _ = leaderboard.save();
+        (leaderboard, place)
+    }
+
+    // The ship enemies shoot at from `from`: the nearest one still in the game
+    fn target(&self, from: (i32, i32)) -> (i32, i32) {
+        let x = self
+            .players
+            .iter()
+            .filter(|player| player.in_game())
+            .min_by_key(|player| (player.x - from.0).abs())
+            .map_or(self.players[0].x, |player| player.x);
+        (x, PLAYER_ROW)
+    }
+
+    // The hand-authored wave for the current level, if the level file has one
+    fn current_wave(&self) -> Option<&Wave> {
+        self.waves.get(self.level - 1)
+    }
+
+    // Boss levels come from the level file, or every fifth level once it runs out.
+    // `%` rather than is_multiple_of, which needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn is_boss_level(&self) -> bool {
+        match self.current_wave() {
+            Some(wave) => wave.boss,
+            None => self.level >= 5 && self.level % 5 == 0,
         }
     }
-    // Load the high score from a file
-    fn load_high_score() -> u32 {
-        if let Ok(mut file) = OpenOptions::new().read(true).open("high_score.txt") {
-            let mut content = String::new();
-            if file.read_to_string(&mut content).is_ok() {
-                if let Ok(score) = content.trim().parse() {
-                    return score;
-                }
+
+    // Ticks between enemy steps
+    fn enemy_move_interval(&self) -> usize {
+        let base = self
+            .difficulty
+            .pace(self.enemy_speed.unwrap_or(20 - self.level.min(15)));
+        match self.mode {
+            GameMode::Arcade => base,
+            GameMode::Classic => {
+                formation::step_interval(base, self.enemies.len(), self.formation_size)
             }
         }
-        0
     }
 
-    // Save the high score to a file
-    fn save_high_score(&self) {
-        if self.score > self.high_score {
-            if let Ok(mut file) = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open("high_score.txt")
-            {
-                let _ = write!(file, "{}", self.score);
-            }
+    // Spawn the current wave's formation, marching right in classic mode
+    fn spawn_wave(&mut self) {
+        self.enemies = self.create_enemies();
+        self.formation_direction = 1;
+        self.formation_size = self.enemies.len();
+    }
+
+    // Rebuild the bunkers and spawn the current level's enemies, and its boss on boss levels
+    fn start_level(&mut self) {
+        let layout = self
+            .current_wave()
+            .and_then(|wave| wave.bunkers.clone())
+            .unwrap_or_default();
+        self.bunkers = Bunkers::build(&layout);
+        // A UFO still crossing when the wave ends flies off with it
+        if self.ufo.take().is_some() {
+            self.schedule_ufo();
+        }
+        let boss_level = self.is_boss_level();
+        if boss_level {
+            self.spawn_boss();
+        }
+        // Authored boss waves may bring escorts, random ones never do
+        if !boss_level || self.current_wave().is_some() {
+            self.spawn_wave();
         }
     }
 
-    // Create enemies based on the current level
+    // Create enemies from the level file, or randomly once it runs out
     fn create_enemies(&mut self) -> Vec<Enemy> {
         let mut enemies = Vec::new();
-        let mut rng = rand::thread_rng();
-        let rows = 1 + self.level / 3;
-        let cols = 3 + self.level / 3;
-
-        for row in 0..rows {
-            for col in 0..cols {
-                let enemy_type = match rng.gen_range(0..8) {
-                    0 => 'Z', // Zigzag
-                    1 => 'W', // Wave
-                    2 => 'D', // Diagonal
-                    3 => 'S', // Shooter
-                    4 => 'T', // Teleporter
-                    5 => 'F', // Fast
-                    6 => 'B', // Bomber
-                    _ => 'N', // Normal
-                };
-                let color = rng.gen_range(1..5);
-                enemies.push(Enemy {
-                    x: col * (WIDTH / (cols + 1)) + 5,
-                    y: row * 2 + 3,
-                    enemy_type,
-                    color,
-                    health: match enemy_type {
-                        'S' | 'T' | 'F' => 2,
-                        'B' => 3,
-                        _ => 1,
-                    },
-                    shoot_timer: rng.gen_range(0..50),
-                });
+        let wave = self.current_wave().cloned();
+        let rng = &mut self.rng;
+
+        if let Some(wave) = wave {
+            self.enemy_speed = wave.speed;
+            for &(x, y, glyph) in &wave.enemies {
+                // Level files are validated when loaded, so every glyph has a kind
+                if let Some(kind) = enemies::kind_for(glyph) {
+                    enemies.push(Enemy::new(x as i32, y as i32, kind, rng));
+                }
+            }
+            for event in &wave.events {
+                match event {
+                    WaveEvent::HealthEnemy => enemies.push(Enemy::drop_in(rng)),
+                    WaveEvent::Powerup(powerup) => {
+                        self.powerups
+                            .push((rng.gen_range(0..WIDTH as i32), 0, *powerup));
+                    }
+                }
+            }
+        } else {
+            self.enemy_speed = None;
+            let rows = 1 + self.level / 3;
+            let cols = 3 + self.level / 3;
+
+            for row in 0..rows {
+                for col in 0..cols {
+                    let kind = enemies::random_kind(rng);
+                    enemies.push(Enemy::new(
+                        (col * (WIDTH / (cols + 1)) + 5) as i32,
+                        (row * 2 + 3) as i32,
+                        kind,
+                        rng,
+                    ));
+                }
             }
         }
 
         // Add a health enemy if it's time
-        if self.last_health_enemy_time.elapsed() >= Duration::from_secs(60) {
-            enemies.push(Enemy {
-                x: rng.gen_range(0..WIDTH),
-                y: 0,
-                enemy_type: 'H',
-                color: 2,
-                health: 1,
-                shoot_timer: 0,
-            });
-            self.last_health_enemy_time = Instant::now();
+        if self.tick - self.last_health_enemy_tick >= ticks(HEALTH_ENEMY_INTERVAL) {
+            enemies.push(Enemy::drop_in(rng));
+            self.last_health_enemy_tick = self.tick;
         }
 
         enemies
@@ -183,16 +378,12 @@ impl Game {
 
     // Create a powerup
     fn create_powerup(&mut self) {
-        let mut rng = rand::thread_rng();
-        if self.last_powerup_time.elapsed() >= Duration::from_secs(30) && self.powerups.is_empty() {
-            let powerup_type = match rng.gen_range(0..3) {
-                0 => 'B', // Bigger Laser
-                1 => 'M', // Multi-directional Laser
-                _ => 'S', // Shield
-            };
-            self.powerups
-                .push((rng.gen_range(0..WIDTH), 0, powerup_type));
-            self.last_powerup_time = Instant::now();
+        if self.tick - self.last_powerup_tick >= ticks(POWERUP_INTERVAL) && self.powerups.is_empty()
+        {
+            let powerup = Powerup::random(&mut self.rng);
+            let x = self.rng.gen_range(0..WIDTH as i32);
+            self.powerups.push((x, 0, powerup));
+            self.last_powerup_tick = self.tick;
         }
     }
 
@@ -201,57 +392,80 @@ impl Game {
         if self.paused {
             return;
         }
+        self.tick += 1;
 
-        // Handle powerup timer
-        if let Some(_) = self.powerup_active {
-            if self.powerup_timer > 0 {
-                self.powerup_timer -= 1;
-            } else {
-                self.powerup_active = None;
-            }
+        // Handle powerup timers
+        for player in &mut self.players {
+            player.tick_powerups();
         }
 
-        // Move bullets and check for collisions
+        // Move bullets and check for collisions along the path each one travelled
+        let slow = self.slow_factor();
+        // Each ship still in the game: its player, cell and whether it is shielded
+        let ships: Vec<(usize, (i32, i32), bool)> = self
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.in_game())
+            .map(|(i, player)| (i, (player.x, PLAYER_ROW), player.shield_active()))
+            .collect();
+        let mut collected = Vec::new();
+        let mut player_hits = Vec::new();
         self.bullets.retain_mut(|bullet| {
-            if bullet.2 {
-                // Enemy bullet
-                bullet.1 += 1;
+            if bullet.is_enemy {
+                bullet.step(1.0 / slow as f32);
             } else {
-                // Player bullet
-                bullet.1 = bullet.1.saturating_sub(1);
+                bullet.step(1.0);
             }
+            let path = bullet.path();
 
-            // Check for collisions with powerups
-            self.powerups.retain(|powerup| {
-                if bullet.0 == powerup.0 && bullet.1 == powerup.1 && !bullet.2 {
-                    self.powerup_active = Some(powerup.2);
-                    self.powerup_timer = 100;
-                    false
-                } else {
-                    true
-                }
-            });
+            // Bunkers stop bullets from both sides
+            if self.bunkers.hit(&path) {
+                return false;
+            }
 
-            // Check for collisions with player
-            if bullet.2
-                && bullet.1 == HEIGHT - 1
-                && (bullet.0 == self.player
-                    || bullet.0 == self.player.saturating_sub(1)
-                    || bullet.0 == self.player + 1)
-            {
-                // Check if player is immune
-                if self
-                    .last_hit_time
-                    .map_or(true, |time| time.elapsed() >= IMMUNITY_DURATION)
-                {
-                    self.lives = self.lives.saturating_sub(1);
-                    self.last_hit_time = Some(Instant::now());
+            if bullet.is_enemy {
+                for &(i, ship, shielded) in &ships {
+                    // The shield absorbs enemy bullets that reach the barrier around the ship
+                    let blocked = path
+                        .iter()
+                        .any(|&(x, y)| y >= PLAYER_ROW - 1 && (x - ship.0).abs() <= 1);
+                    if shielded && blocked {
+                        self.events.push(GameEvent::ShieldBlock);
+                        return false;
+                    }
+
+                    // Check for collisions with the ship
+                    if collision::paths_meet(&path, &[ship], 1) {
+                        let cause = bullet.fired_by.map_or(Cause::BossShot, Cause::Shot);
+                        player_hits.push((i, cause));
+                        return false;
+                    }
                 }
-                false
             } else {
-                bullet.1 > 0 && bullet.1 < HEIGHT
+                // Check for collisions with powerups, they go to whoever shot them
+                self.powerups.retain(|powerup| {
+                    if path.contains(&(powerup.0, powerup.1)) {
+                        collected.push((bullet.owner, powerup.2));
+                        false
+                    } else {
+                        true
+                    }
+                });
             }
+
+            let (x, y) = bullet.cell();
+            !world::bullet_despawns(x, y)
         });
+        for (i, cause) in player_hits {
+            self.hurt_player(i, cause);
+        }
+        // Powerups shot by a player who is out of the game are lost
+        for (i, powerup) in collected {
+            if self.players[i].in_game() {
+                self.collect_powerup(i, powerup);
+            }
+        }
 
         // Move powerups
         self.powerup_move_counter += 1;
@@ -260,161 +474,261 @@ impl Game {
             for powerup in &mut self.powerups {
                 powerup.1 += 1;
             }
-            self.powerups.retain(|powerup| powerup.1 < HEIGHT);
+            self.powerups
+                .retain(|powerup| !world::powerup_despawns(powerup.1));
         }
 
-        // Check for collisions and update enemies
-        let mut rng = rand::thread_rng();
+        // Check for collisions and update enemies. Enemies moved at the end of the
+        // last tick, before bullets moved at the start of this one
+        let mut spent = vec![false; self.bullets.len()]; // Player bullets stop at the first hit
+        let mut volleys = Vec::new();
         self.enemies.retain_mut(|enemy| {
             let mut hit = false;
-            for bullet in &self.bullets {
-                if !bullet.2
-                    && (bullet.0.saturating_sub(LASER_HITBOX_WIDTH / 2)
-                        ..=bullet.0.saturating_add(LASER_HITBOX_WIDTH / 2))
-                        .contains(&enemy.x)
-                    && bullet.1 == enemy.y
+            let enemy_path = collision::enemy_path(enemy);
+            enemy.last_position = (enemy.x, enemy.y);
+            for (i, bullet) in self.bullets.iter().enumerate() {
+                if bullet.is_enemy
+                    || spent[i]
+                    || !collision::turns_meet(
+                        &enemy_path,
+                        &bullet.path(),
+                        LASER_HITBOX_WIDTH as i32 / 2,
+                    )
                 {
-                    enemy.health -= 1;
-                    if enemy.health == 0 {
-                        hit = true;
-                        self.explosions.push((enemy.x, enemy.y, 0));
-                        if enemy.enemy_type == 'H' {
-                            self.lives = (self.lives + 1).min(5);
-                        }
-                        self.score += match enemy.enemy_type {
-                            'S' | 'T' | 'F' => 20,
-                            'B' => 30,
-                            'H' => 50,
-                            _ => 10,
-                        };
+                    continue;
+                }
+                spent[i] = true;
+                enemy.health -= 1;
+                if enemy.health == 0 {
+                    hit = true;
+                    self.explosions.push((enemy.x, enemy.y, 0));
+                    let player = &mut self.players[bullet.owner];
+                    player.score += enemy.kind.score_value() * player.score_multiplier();
+                    if player.in_game() {
+                        player.lives = (player.lives + enemy.kind.bonus_lives()).min(5);
                     }
-                    break;
                 }
+                self.events.push(GameEvent::EnemyHit { destroyed: hit });
+                break;
             }
 
-            // Enemy shooting
-            if enemy.enemy_type == 'S' || enemy.enemy_type == 'B' {
+            // Enemy shooting, classic formations fire together below
+            if let (GameMode::Arcade, Some(interval)) = (self.mode, enemy.kind.fire_interval()) {
                 enemy.shoot_timer += 1;
-                if enemy.shoot_timer >= 50 {
+                if enemy.shoot_timer >= self.difficulty.fire_interval(interval, slow) {
                     enemy.shoot_timer = 0;
-                    if self.bullets.len() < 10 {
-                        self.bullets.push((enemy.x, enemy.y + 1, true));
-                        if enemy.enemy_type == 'B' {
-                            // Bomber shoots in 3 directions
-                            self.bullets
-                                .push((enemy.x.saturating_sub(1), enemy.y + 1, true));
-                            self.bullets.push((enemy.x + 1, enemy.y + 1, true));
-                        }
-                    }
+                    volleys.push((
+                        enemy.kind.pattern(),
+                        (enemy.x, enemy.y + 1),
+                        enemy.kind.glyph(),
+                    ));
                 }
             }
 
             !hit
         });
+        if self.mode == GameMode::Classic && !self.enemies.is_empty() {
+            self.formation_shoot_timer += 1;
+            let interval = self.difficulty.fire_interval(FORMATION_FIRE_INTERVAL, slow);
+            if self.formation_shoot_timer >= interval {
+                self.formation_shoot_timer = 0;
+                let shooters = formation::shooters(&self.enemies);
+                let enemy = &self.enemies[shooters[self.rng.gen_range(0..shooters.len())]];
+                volleys.push((
+                    enemy.kind.pattern(),
+                    (enemy.x, enemy.y + 1),
+                    enemy.kind.glyph(),
+                ));
+            }
+        }
+        for (pattern, origin, glyph) in volleys {
+            if self.bullets.len() < 10 {
+                self.fire_pattern(pattern, origin, Some(glyph));
+            }
+        }
 
         // Move enemies
         self.enemy_move_counter += 1;
-        if self.enemy_move_counter >= 20 - self.level.min(15) {
+        if self.enemy_move_counter >= self.enemy_move_interval() * slow {
             self.enemy_move_counter = 0;
             if self.enemies.is_empty() && self.boss.is_none() {
                 self.level += 1;
-                self.lives = (self.lives + 1).min(5); // Give player an extra life after beating a level
-                if self.level >= 5 && self.level % 5 == 0 {
-                    self.spawn_boss();
-                } else {
-                    self.enemies = self.create_enemies();
+                // Give every player still in the game an extra life after beating a level
+                for player in self.players.iter_mut().filter(|player| player.in_game()) {
+                    player.lives = (player.lives + 1).min(5);
                 }
+                self.start_level();
             } else {
-                for enemy in &mut self.enemies {
-                    match enemy.enemy_type {
-                        'Z' => {
-                            enemy.x =
-                                (enemy.x + if enemy.y % 4 < 2 { 1 } else { WIDTH - 1 }) % WIDTH;
-                            enemy.y += 1;
-                        }
-                        'W' => {
-                            enemy.x = (enemy.x + (enemy.y as f32 / 2.0).sin() as usize + 1) % WIDTH;
-                            enemy.y += 1;
-                        }
-                        'D' => {
-                            enemy.x = (enemy.x + 1) % WIDTH;
-                            enemy.y += 1;
-                        }
-                        'T' => {
-                            if rng.gen_bool(0.1) {
-                                enemy.x = rng.gen_range(0..WIDTH);
-                                enemy.y = rng.gen_range(0..HEIGHT / 2);
-                            } else {
-                                enemy.y += 1;
-                            }
-                        }
-                        'F' => {
-                            enemy.y += 2;
+                match self.mode {
+                    GameMode::Arcade => {
+                        for enemy in &mut self.enemies {
+                            let kind = enemy.kind;
+                            kind.step(enemy, &mut self.rng);
                         }
-                        'H' | 'S' | 'B' | 'N' => {
-                            enemy.y += 1;
+                    }
+                    GameMode::Classic => {
+                        self.formation_direction =
+                            formation::march(&mut self.enemies, self.formation_direction);
+                    }
+                }
+                for enemy in &self.enemies {
+                    self.bunkers.erase(&collision::enemy_path(enemy));
+                }
+
+                // Enemies that run into a ship crash, costing a life unless shielded
+                let mut crashes = Vec::new();
+                self.enemies.retain(|enemy| {
+                    let path = collision::enemy_path(enemy);
+                    match ships
+                        .iter()
+                        .find(|(_, ship, _)| collision::paths_meet(&path, &[*ship], 1))
+                    {
+                        Some(&(i, _, shielded)) => {
+                            self.explosions.push((enemy.x, enemy.y.min(PLAYER_ROW), 0));
+                            crashes.push((i, shielded, enemy.kind.glyph()));
+                            false
                         }
-                        _ => {}
+                        None => true,
                     }
-                    if enemy.y >= HEIGHT - 1 {
-                        // Check if player is immune
-                        if self
-                            .last_hit_time
-                            .map_or(true, |time| time.elapsed() >= IMMUNITY_DURATION)
-                        {
-                            self.lives = self.lives.saturating_sub(1);
-                            self.last_hit_time = Some(Instant::now());
+                });
+                for (i, shielded, glyph) in crashes {
+                    self.events.push(GameEvent::ShipCollision);
+                    if !shielded {
+                        self.hurt_player(i, Cause::Rammed(glyph));
+                    }
+                }
+
+                // Any shield guards the bottom row, otherwise an invasion hurts everyone
+                let shielded = ships.iter().any(|&(_, _, shielded)| shielded);
+                if let Some(invader) = self.enemies.iter().find(|enemy| enemy.y >= PLAYER_ROW) {
+                    if shielded {
+                        // The shield destroys enemies that reach the bottom instead of costing a life
+                        for enemy in self.enemies.iter().filter(|e| e.y >= PLAYER_ROW) {
+                            self.explosions.push((enemy.x, PLAYER_ROW, 0));
+                        }
+                        self.enemies.retain(|enemy| enemy.y < PLAYER_ROW);
+                    } else {
+                        let cause = Cause::Invasion(invader.kind.glyph());
+                        for &(i, _, _) in &ships {
+                            self.hurt_player(i, cause);
                         }
-                        self.enemies = self.create_enemies();
-                        break;
+                        self.spawn_wave();
                     }
                 }
             }
         }
 
         // Update boss
-        if let Some(boss) = &mut self.boss {
-            boss.shoot_timer += 1;
-            if boss.shoot_timer >= 20 {
-                boss.shoot_timer = 0;
-                if self.bullets.len() < 15 {
-                    self.bullets.push((boss.x, boss.y + 1, true));
-                    if boss.phase >= 2 {
-                        self.bullets
-                            .push((boss.x.saturating_sub(2), boss.y + 1, true));
-                        self.bullets.push((boss.x + 2, boss.y + 1, true));
+        let mut boss_volley = Vec::new();
+        let boss_target = self.boss.as_ref().map(|boss| self.target((boss.x, boss.y)));
+        if let (Some(boss), Some(target)) = (&mut self.boss, boss_target) {
+            if boss.tick(target.0, slow) {
+                boss_volley = boss.fire();
+                // Bosses with hangars send in reinforcements, up to a point
+                for (x, y) in boss.launch() {
+                    if self.enemies.len() < 8 {
+                        let kind = enemies::random_kind(&mut self.rng);
+                        self.enemies.push(Enemy::new(x, y, kind, &mut self.rng));
                     }
                 }
             }
-            // Boss movement
-            boss.move_timer += 1;
-            if boss.move_timer >= 12 {
-                boss.move_timer = 0;
-                if boss.x == 0 || boss.x == WIDTH - 1 {
-                    boss.direction *= -1;
+            // Check for collisions with the boss's hull and parts
+            for (i, bullet) in self.bullets.iter().enumerate() {
+                if bullet.is_enemy || spent[i] {
+                    continue;
+                }
+                let (kind, destroyed, center) = match boss.hit(&bullet.path()) {
+                    Some(BossHit::Part {
+                        kind,
+                        destroyed,
+                        center,
+                    }) => (kind, destroyed, center),
+                    Some(BossHit::Hull) => {
+                        spent[i] = true;
+                        continue;
+                    }
+                    None => continue,
+                };
+                spent[i] = true;
+                self.events.push(GameEvent::BossHit {
+                    destroyed: destroyed && kind == PartKind::Core,
+                });
+                if destroyed {
+                    let player = &mut self.players[bullet.owner];
+                    player.score += kind.score_value() * player.score_multiplier();
+                    self.explosions.push((center.0, center.1, 0));
+                }
+                if boss.is_destroyed() {
+                    for part in &boss.parts {
+                        let (x, y) = boss.part_center(part);
+                        self.explosions.push((x, y, 0));
+                    }
+                    self.boss = None;
+                    break;
                 }
-                boss.x = (boss.x as i32 + boss.direction as i32)
-                    .max(0)
-                    .min(WIDTH as i32 - 1) as usize;
             }
-            // Check for collisions with boss
-            for bullet in &self.bullets {
-                if !bullet.2
-                    && (bullet.0.saturating_sub(2)..=bullet.0.saturating_add(2)).contains(&boss.x)
-                    && bullet.1 == boss.y
+        }
+
+        if self.bullets.len() < 15 {
+            for (pattern, origin) in boss_volley {
+                self.fire_pattern(pattern, origin, None);
+            }
+        }
+
+        // The UFO crosses now and then while a wave is being fought
+        match &mut self.ufo {
+            Some(ufo) => {
+                if !ufo.tick(slow) {
+                    self.ufo = None;
+                    self.schedule_ufo();
+                }
+            }
+            None => {
+                if self.tick >= self.next_ufo_tick
+                    && self.boss.is_none()
+                    && !self.enemies.is_empty()
                 {
-                    boss.health = boss.health.saturating_sub(1);
-                    if boss.health == 0 {
-                        self.score += 1000;
-                        self.explosions.push((boss.x, boss.y, 0));
-                        self.boss = None;
-                        break;
-                    } else if boss.health == boss.max_health / 2 {
-                        boss.phase = 2;
-                    }
+                    self.ufo = Some(Ufo::new(self.rng.gen_bool(0.5)));
+                    self.events.push(GameEvent::UfoAppeared);
                 }
             }
         }
+        let ufo_hit = self.ufo.as_ref().and_then(|ufo| {
+            let bullets = self.bullets.iter().enumerate();
+            bullets
+                .filter(|&(i, bullet)| !bullet.is_enemy && !spent[i])
+                .find(|(_, bullet)| ufo.hit(&bullet.path()))
+                .map(|(i, bullet)| (i, bullet.owner, ufo.center()))
+        });
+        if let Some((i, owner, (x, y))) = ufo_hit {
+            spent[i] = true;
+            let player = &mut self.players[owner];
+            let points = ufo::score(player.shots) * player.score_multiplier();
+            player.score += points;
+            self.explosions.push((x, y, 0));
+            self.popups.push((x, y, points, 0));
+            self.events.push(GameEvent::UfoHit { points });
+            self.ufo = None;
+            self.schedule_ufo();
+        }
+
+        // Remove player bullets that hit something
+        let mut index = 0;
+        self.bullets.retain(|_| {
+            index += 1;
+            spent.get(index - 1) != Some(&true)
+        });
+
+        // Release pattern bullets whose wait is over
+        let tick = self.tick;
+        let due: Vec<Bullet> = self
+            .pending_bullets
+            .iter()
+            .filter(|(at, _)| *at <= tick)
+            .map(|(_, bullet)| bullet.clone())
+            .collect();
+        self.pending_bullets.retain(|(at, _)| *at > tick);
+        self.bullets.extend(due);
 
         // Move explosions
         for explosion in &mut self.explosions {
@@ -422,23 +736,41 @@ impl Game {
         }
         self.explosions.retain(|explosion| explosion.2 < 3);
 
+        // Float score popups away
+        for popup in &mut self.popups {
+            popup.3 += 1;
+        }
+        self.popups.retain(|popup| popup.3 < POPUP_TICKS);
+
         // Create powerups
         self.create_powerup();
     }
 
-    // Spawn a boss
+    // Fire a bullet pattern at the player, from the enemy kind with glyph `fired_by`
+    // or else the boss. Bullets the pattern holds back wait in pending_bullets until
+    // their tick comes
+    fn fire_pattern(&mut self, pattern: &str, origin: (i32, i32), fired_by: Option<char>) {
+        let target = self.target(origin);
+        for (delay, mut bullet) in self.patterns.fire(pattern, origin, target) {
+            bullet.fired_by = fired_by;
+            if delay == 0 {
+                self.bullets.push(bullet);
+            } else {
+                self.pending_bullets
+                    .push((self.tick + delay as u64, bullet));
+            }
+        }
+    }
+
+    // Pick when the next UFO comes
+    fn schedule_ufo(&mut self) {
+        let interval = ticks(UFO_MIN_INTERVAL)..=ticks(UFO_MAX_INTERVAL);
+        self.next_ufo_tick = self.tick + self.rng.gen_range(interval);
+    }
+
+    // Spawn the boss whose turn it is
     fn spawn_boss(&mut self) {
-        let max_health = (25 + self.level as u16 * 5).max(1); // Halved from 50 + self.level * 10
-        self.boss = Some(Boss {
-            x: WIDTH / 2,
-            y: 3,
-            health: max_health,
-            max_health,
-            phase: 1,
-            shoot_timer: 0,
-            direction: 1,
-            move_timer: 0,
-        });
+        self.boss = Some(Boss::new(bosses::for_level(self.level), self.level));
     }
 
     // Render the game state as a string
@@ -449,187 +781,482 @@ impl Game {
             let elapsed = self.start_time.elapsed();
             let minutes = elapsed.as_secs() / 60;
             let seconds = elapsed.as_secs() % 60;
+            let level = match self.current_wave() {
+                Some(wave) if !wave.name.is_empty() => format!("{} ({})", self.level, wave.name),
+                _ => self.level.to_string(),
+            };
+            let powerup = |player: &Player| match (player.powerup_flash, player.powerup_active) {
+                (Some((powerup, _)), _) => format!(
+                    " | {}{}!{}",
+                    color::Fg(powerup.color()),
+                    powerup.name(),
+                    color::Fg(color::Yellow)
+                ),
+                (None, Some(powerup)) => format!(
+                    " | {}{}: {:.1}s{}",
+                    color::Fg(powerup.color()),
+                    powerup.name(),
+                    player.powerup_time_left().as_secs_f32(),
+                    color::Fg(color::Yellow)
+                ),
+                (None, None) => String::new(),
+            };
+
+            if let [player] = &self.players[..] {
+                output.push_str(&format!(
+                    "{}Score: {} | High Score: {} | Level: {} | Lives: {} | Time: {:02}:{:02}{}{}\r\n",
+                    color::Fg(color::Yellow),
+                    player.score,
+                    self.high_score,
+                    level,
+                    "♥".repeat(player.lives),
+                    minutes,
+                    seconds,
+                    powerup(player),
+                    color::Fg(color::Reset)
+                ));
+            } else {
+                // Co-op shares one line: each player's score, lives and powerup first
+                output.push_str(&format!("{}", color::Fg(color::Yellow)));
+                for (i, player) in self.players.iter().enumerate() {
+                    output.push_str(&format!(
+                        "{}P{}{}: {} {}{} | ",
+                        color::Fg(player::colors(i).0),
+                        i + 1,
+                        color::Fg(color::Yellow),
+                        player.score,
+                        "♥".repeat(player.lives),
+                        powerup(player)
+                    ));
+                }
+                output.push_str(&format!(
+                    "High Score: {} | Level: {} | Time: {:02}:{:02}{}\r\n",
+                    self.high_score,
+                    level,
+                    minutes,
+                    seconds,
+                    color::Fg(color::Reset)
+                ));
+            }
+        }
 
-            output.push_str(&format!(
-                "{}Score: {} | High Score: {} | Level: {} | Lives: {} | Time: {:02}:{:02}{}\r\n",
-                color::Fg(color::Yellow),
-                self.score,
-                self.high_score,
-                self.level,
-                "♥".repeat(self.lives),
-                minutes,
-                seconds,
-                color::Fg(color::Reset)
-            ));
+        // Convert screen to string with colors
+        for row in &self.playfield() {
+            for &(ch, cell_color) in row {
+                output.push_str(&format!("{}", color::Fg(cell_color)));
+                output.push(ch);
+            }
+            output.push_str(&format!("{}\r\n", color::Fg(color::Reset)));
         }
 
-        let mut screen = vec![vec![' '; WIDTH]; HEIGHT];
+        output
+    }
 
-        // Draw player
-        if !self.paused {
-            screen[HEIGHT - 1][self.player] = if self
-                .last_hit_time
-                .map_or(false, |time| time.elapsed() < IMMUNITY_DURATION)
-            {
-                '△' // Invincible player
+    // The playfield as it is drawn, a glyph and its color for every cell
+    fn playfield(&self) -> Vec<Vec<ScreenCell>> {
+        // Everything is drawn through `plot`, which clips world positions that are
+        // off-screen
+        let blank: ScreenCell = (' ', &color::Reset);
+        let mut screen = vec![vec![blank; WIDTH]; HEIGHT];
+        let mut plot = |x: i32, y: i32, cell: ScreenCell| {
+            if let Some((x, y)) = world::to_screen(x, y) {
+                screen[y][x] = cell;
+            }
+        };
+
+        // Draw the players still in the game
+        let ships = self.players.iter().enumerate();
+        for (i, player) in ships.filter(|(_, player)| !self.paused && player.in_game()) {
+            let (normal, immune) = player::colors(i);
+            let ship: ScreenCell = if player.is_immune(self.tick) {
+                ('△', immune) // Invincible player
             } else {
-                'A' // Normal player
+                ('A', normal) // Normal player
             };
+            plot(player.x, PLAYER_ROW, ship);
+
+            // Draw the shield barrier around the ship
+            if player.shield_active() {
+                let barrier: &dyn color::Color = &color::LightCyan;
+                plot(player.x - 1, PLAYER_ROW, ('(', barrier));
+                plot(player.x - 1, PLAYER_ROW - 1, ('/', barrier));
+                plot(player.x + 1, PLAYER_ROW, (')', barrier));
+                plot(player.x + 1, PLAYER_ROW - 1, ('\\', barrier));
+                plot(player.x, PLAYER_ROW - 1, ('‾', barrier));
+            }
+        }
+
+        // Draw bunkers
+        if !self.paused {
+            for (x, y, glyph) in self.bunkers.cells() {
+                plot(x, y, (glyph, &color::Green));
+            }
         }
 
         // Draw enemies
         if !self.paused {
             for enemy in &self.enemies {
-                if enemy.y < HEIGHT {
-                    screen[enemy.y][enemy.x] = enemy.enemy_type;
-                }
+                plot(enemy.x, enemy.y, (enemy.kind.glyph(), enemy.kind.color()));
             }
         }
 
         // Draw boss
         if let Some(boss) = &self.boss {
-            screen[boss.y][boss.x] = 'B';
+            for (x, y, glyph, cell_color) in boss.cells() {
+                plot(x, y, (glyph, cell_color));
+            }
             // Draw boss health bar
             let health_bar_width = 20;
-            let health_percentage = boss.health as f32 / boss.max_health as f32;
-            let filled_width = (health_percentage * health_bar_width as f32) as usize;
+            let health_percentage = boss.health_fraction();
+            let filled_width = (health_percentage * health_bar_width as f32) as i32;
             for i in 0..health_bar_width {
-                screen[1][i + (WIDTH - health_bar_width) / 2] =
-                    if i < filled_width { '█' } else { '░' };
+                let cell: ScreenCell = if i < filled_width {
+                    ('█', &color::Green)
+                } else {
+                    ('░', &color::Red)
+                };
+                plot(i + (WIDTH as i32 - health_bar_width) / 2, 1, cell);
+            }
+            // Announce the boss while it makes its entrance
+            if boss.intro_ticks > 0 {
+                let banner: [(&str, &dyn color::Color); 2] = [
+                    (boss.kind.name(), &color::LightRed),
+                    (boss.kind.intro(), &color::White),
+                ];
+                for (row, (text, text_color)) in banner.into_iter().enumerate() {
+                    let left = (WIDTH as i32 - text.chars().count() as i32) / 2;
+                    let y = HEIGHT as i32 / 2 + 2 * row as i32 - 1;
+                    for (i, c) in text.chars().enumerate() {
+                        plot(left + i as i32, y, (c, text_color));
+                    }
+                }
+            }
+        }
+
+        // Draw the UFO
+        if let (false, Some(ufo)) = (self.paused, &self.ufo) {
+            for (x, y, glyph) in ufo.cells() {
+                plot(x, y, (glyph, &color::LightMagenta));
             }
         }
 
         // Draw bullets
         if !self.paused {
-            for &(x, y, is_enemy) in &self.bullets {
-                if y < HEIGHT {
-                    screen[y][x] = if is_enemy { '↓' } else { '|' };
-                }
+            for bullet in &self.bullets {
+                let (x, y) = bullet.cell();
+                plot(x, y, (bullet.glyph(), bullet.color()));
             }
         }
 
         // Draw powerups
         if !self.paused {
-            for &(x, y, powerup_type) in &self.powerups {
-                if y < HEIGHT {
-                    screen[y][x] = powerup_type;
-                }
+            for &(x, y, powerup) in &self.powerups {
+                plot(x, y, (powerup.glyph(), powerup.color()));
             }
         }
 
         // Draw explosions
         if !self.paused {
             for &(x, y, frame) in &self.explosions {
-                if y < HEIGHT {
-                    screen[y][x] = match frame {
-                        0 => '*',
-                        1 => '+',
-                        _ => ' ',
-                    };
-                }
+                let cell: ScreenCell = match frame {
+                    0 => ('*', &color::Red),
+                    1 => ('+', &color::Red),
+                    _ => blank,
+                };
+                plot(x, y, cell);
             }
         }
 
-        // Convert screen to string with colors
-        for (_y, row) in screen.iter().enumerate() {
-            for (_x, &ch) in row.iter().enumerate() {
-                match ch {
-                    'A' => output.push_str(&format!("{}", color::Fg(color::Blue))),
-                    '△' => output.push_str(&format!("{}", color::Fg(color::LightBlue))),
-                    'Z' => output.push_str(&format!("{}", color::Fg(color::LightRed))),
-                    'W' => output.push_str(&format!("{}", color::Fg(color::LightMagenta))),
-                    'D' => output.push_str(&format!("{}", color::Fg(color::LightYellow))),
-                    'H' => output.push_str(&format!("{}", color::Fg(color::Green))),
-                    'S' => output.push_str(&format!("{}", color::Fg(color::Red))),
-                    'T' => output.push_str(&format!("{}", color::Fg(color::Cyan))),
-                    'F' => output.push_str(&format!("{}", color::Fg(color::LightBlue))),
-                    'B' => output.push_str(&format!("{}", color::Fg(color::LightCyan))),
-                    'N' => output.push_str(&format!("{}", color::Fg(color::White))),
-                    '|' => output.push_str(&format!("{}", color::Fg(color::Green))),
-                    '↓' => output.push_str(&format!("{}", color::Fg(color::Red))),
-                    '*' | '+' => output.push_str(&format!("{}", color::Fg(color::Red))),
-                    'M' => output.push_str(&format!("{}", color::Fg(color::LightGreen))),
-                    '█' => output.push_str(&format!("{}", color::Fg(color::Green))),
-                    '░' => output.push_str(&format!("{}", color::Fg(color::Red))),
-                    _ => output.push_str(&format!("{}", color::Fg(color::Reset))),
+        // Draw score popups, fading as they rise
+        if !self.paused {
+            for &(x, y, points, age) in &self.popups {
+                let text = points.to_string();
+                let popup_color: &dyn color::Color = if age < POPUP_TICKS * 2 / 3 {
+                    &color::LightMagenta
+                } else {
+                    &color::LightBlack
+                };
+                let left = x - text.len() as i32 / 2;
+                let y = y - (age / POPUP_RISE_TICKS) as i32;
+                for (i, c) in text.chars().enumerate() {
+                    plot(left + i as i32, y, (c, popup_color));
                 }
-                output.push(ch);
             }
-            output.push_str(&format!("{}\r\n", color::Fg(color::Reset)));
         }
 
-        output
+        screen
     }
 
     // Handle user input
-    fn handle_input(&mut self, key: Key, laser_sink: &Sink) {
-        match key {
-            Key::Left => {
-                if !self.paused {
-                    self.player = self.player.saturating_sub(1)
-                }
-            }
-            Key::Right => {
-                if !self.paused {
-                    self.player = (self.player + 1).min(WIDTH - 1)
-                }
-            }
-            Key::Char(' ') => {
-                if !self.paused && self.bullets.iter().filter(|&b| !b.2).count() < 3 {
-                    // Play laser sound
-                    let laser_path = get_asset_path("laser.mp3");
-                    let file = BufReader::new(File::open(&laser_path).unwrap());
-                    let source = Decoder::new(file).unwrap();
-                    laser_sink.append(source);
-
-                    match self.powerup_active {
-                        Some('B') => {
-                            // Bigger Laser
-                            self.bullets.push((self.player, HEIGHT - 2, false));
+    fn handle_input(&mut self, key: Key) {
+        match (key, player::action_for(key)) {
+            (Key::Char('p') | Key::Char('P'), _) => {
+                self.paused = !self.paused;
+            }
+            (_, Some((index, action))) => self.handle_action(index, action),
+            _ => {}
+        }
+    }
+
+    // Carry out a player's action, if they are still in the game
+    fn handle_action(&mut self, index: usize, action: Action) {
+        let in_game = self.players.get(index).is_some_and(Player::in_game);
+        if self.paused || !in_game {
+            return;
+        }
+        self.act(index, action);
+
+        // Check for powerup collection
+        let mut collected = Vec::new();
+        let players = &self.players;
+        self.powerups.retain(|&powerup| {
+            let catcher = players.iter().position(|player| {
+                player.in_game() && powerup.0 == player.x && powerup.1 == PLAYER_ROW
+            });
+            match catcher {
+                Some(i) => {
+                    collected.push((i, powerup.2));
+                    false
+                }
+                None => true,
+            }
+        });
+        for (i, powerup) in collected {
+            self.collect_powerup(i, powerup);
+        }
+    }
+
+    // Move or fire one player's ship
+    fn act(&mut self, index: usize, action: Action) {
+        let player = &mut self.players[index];
+        let x = player.x;
+        match action {
+            Action::Left => player.x = world::clamp_x(x - 1),
+            Action::Right => player.x = world::clamp_x(x + 1),
+            Action::Fire => {
+                let own_bullets = self
+                    .bullets
+                    .iter()
+                    .filter(|b| !b.is_enemy && b.owner == index)
+                    .count();
+                if own_bullets >= player.max_bullets() {
+                    return;
+                }
+                let fired_before = self.bullets.len();
+                match player.powerup_active {
+                    Some(Powerup::BiggerLaser) => {
+                        // Bigger Laser, shots past the edge despawn like any other
+                        for x in [x, x - 1, x + 1] {
                             self.bullets
-                                .push((self.player.saturating_sub(1), HEIGHT - 2, false));
-                            self.bullets.push((
-                                (self.player + 1).min(WIDTH - 1),
-                                HEIGHT - 2,
-                                false,
-                            ));
+                                .push(Bullet::player(x, PLAYER_ROW - 1).with_owner(index));
                         }
-                        Some('M') => {
-                            // Multi-directional Laser
-                            self.bullets.push((self.player, HEIGHT - 2, false));
-                            self.bullets
-                                .push((self.player.saturating_sub(1), HEIGHT - 2, false));
-                            self.bullets.push((self.player + 1, HEIGHT - 2, false));
+                    }
+                    Some(Powerup::MultiLaser) => {
+                        // Multi-directional Laser fires a spread
+                        for vx in [-0.5, 0.0, 0.5] {
+                            let bullet = Bullet::new(x, PLAYER_ROW - 1, vx, -1.0, false);
+                            self.bullets.push(bullet.with_owner(index));
                         }
-                        _ => self.bullets.push((self.player, HEIGHT - 2, false)),
                     }
+                    _ => self
+                        .bullets
+                        .push(Bullet::player(x, PLAYER_ROW - 1).with_owner(index)),
                 }
+                player.shots += 1;
+                self.events.push(GameEvent::Shot {
+                    bullets: self.bullets.len() - fired_before,
+                });
             }
-            Key::Char('p') | Key::Char('P') => {
-                self.paused = !self.paused;
+        }
+    }
+
+    // Apply a powerup a player picked up or shot
+    fn collect_powerup(&mut self, index: usize, powerup: Powerup) {
+        self.events.push(GameEvent::PowerupCollected(powerup));
+        let player = &mut self.players[index];
+        if powerup.is_instant() {
+            player.powerup_flash = Some((powerup, 20));
+        }
+        match powerup {
+            Powerup::SmartBomb => self.detonate_smart_bomb(index),
+            Powerup::ExtraLife => player.lives = (player.lives + 1).min(5),
+            _ => {
+                player.powerup_active = Some(powerup);
+                player.powerup_timer = POWERUP_DURATION_TICKS; // Lasts for a few seconds
             }
-            _ => {}
         }
+    }
 
-        // Check for powerup collection
-        if !self.paused {
-            self.powerups.retain(|&powerup| {
-                if powerup.0 == self.player && powerup.1 == HEIGHT - 1 {
-                    self.powerup_active = Some(powerup.2);
-                    self.powerup_timer = 100; // Lasts for a few seconds
-                    return false;
-                }
-                true
-            });
+    // Destroy every enemy and enemy bullet on screen, and hurt the boss. The points
+    // go to the player who set it off
+    fn detonate_smart_bomb(&mut self, index: usize) {
+        let player = &mut self.players[index];
+        let multiplier = player.score_multiplier();
+        for enemy in self.enemies.drain(..) {
+            self.explosions.push((enemy.x, enemy.y, 0));
+            self.events.push(GameEvent::EnemyHit { destroyed: true });
+            player.lives = (player.lives + enemy.kind.bonus_lives()).min(5);
+            player.score += enemy.kind.score_value() * multiplier;
+        }
+        self.bullets.retain(|bullet| !bullet.is_enemy);
+        self.pending_bullets.clear();
+        // The boss survives a bomb, but its turrets and shield take damage
+        if let Some(boss) = &mut self.boss {
+            boss.bomb(3);
+            for part in &boss.parts {
+                let (x, y) = boss.part_center(part);
+                self.explosions.push((x, y, 0));
+            }
+        }
+    }
+
+    // Take a life from a player unless they are still immune from the last hit
+    fn hurt_player(&mut self, index: usize, cause: Cause) {
+        let player = &mut self.players[index];
+        if player.in_game() && !player.is_immune(self.tick) {
+            player.lives -= 1;
+            player.last_hit_tick = Some(self.tick);
+            self.events.push(GameEvent::PlayerHit(cause));
+        }
+    }
+
+    // Enemies and their bullets run this many times slower while anyone has Time Slow
+    fn slow_factor(&self) -> usize {
+        let slowed = self
+            .players
+            .iter()
+            .any(|player| player.in_game() && player.powerup_active == Some(Powerup::TimeSlow));
+        if slowed {
+            2
+        } else {
+            1
         }
     }
 
-    // Check if the game is over
+    // The game is over once every player is out of lives, or any player in versus
     fn is_game_over(&self) -> bool {
-        self.lives == 0
+        if self.versus {
+            !self.players.iter().all(Player::in_game)
+        } else {
+            !self.players.iter().any(Player::in_game)
+        }
     }
 }
 
+// Run the game loop until the game ends. Preview games stop once their wave is cleared.
+// Without a sink the game is silent
+fn run_game(
+    screen: &mut impl Write,
+    rx: &mpsc::Receiver<Key>,
+    laser_sink: Option<&Sink>,
+    game: &mut Game,
+    preview: bool,
+    mut spectators: Option<&mut Spectators>,
+    mut pilot: Option<&mut dyn Agent>,
+) -> io::Result<GameExit> {
+    let mut last_update = Instant::now();
+    let first_level = game.level;
+    let mut debug = DebugOverlay::default();
+
+    loop {
+        // Update game state every 50ms
+        if last_update.elapsed() >= TICK_DURATION {
+            let started = Instant::now();
+            if let Some(agent) = pilot.as_deref_mut() {
+                agent::steer(game, 0, agent);
+            }
+            game.update();
+            let updated = Instant::now();
+            let frame = game.render();
+            debug.timed(updated - started, updated.elapsed());
+            if let Some(spectators) = spectators.as_deref_mut() {
+                spectators.record(&game.events);
+                spectators.show(&frame, game.paused);
+            }
+            handle_events(game, laser_sink);
+            write!(screen, "{}{}", termion::clear::All, frame)?;
+            debug.draw(screen, game)?;
+            if game.paused {
+                display_pause_screen(screen)?;
+            }
+            screen.flush()?;
+            last_update = Instant::now();
+
+            // Check if the game is over
+            if game.is_game_over() {
+                return Ok(GameExit::GameOver);
+            }
+            if preview && game.level > first_level {
+                return Ok(GameExit::Cleared);
+            }
+        }
+
+        // Handle user input. Without any, nobody is left to play
+        let key = match rx.try_recv() {
+            Ok(key) => Some(key),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => return Ok(GameExit::Quit),
+        };
+        if let Some(key) = key {
+            match key {
+                Key::Ctrl('c') => return Ok(GameExit::Quit),
+                Key::Esc if preview => return Ok(GameExit::Stopped),
+                debug_overlay::TOGGLE_KEY => debug.shown = !debug.shown,
+                // Player one's keys do nothing while the autopilot is flying their ship
+                key if pilot.is_some()
+                    && player::action_for(key).is_some_and(|(index, _)| index == 0) => {}
+                key => {
+                    game.handle_input(key);
+                    if let Some(spectators) = spectators.as_deref_mut() {
+                        spectators.record(&game.events);
+                    }
+                    handle_events(game, laser_sink);
+                }
+            }
+        }
+
+        // Small sleep to prevent CPU hogging
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Play sounds for what happened since the last call, if there is a sink to play
+// them on, and add it to the stats
+fn handle_events(game: &mut Game, laser_sink: Option<&Sink>) {
+    for event in game.events.drain(..) {
+        if let Some(laser_sink) = laser_sink {
+            play_sound(laser_sink, &event);
+        }
+        game.stats.record(&event);
+    }
+}
+
+fn play_sound(laser_sink: &Sink, event: &GameEvent) {
+    match event {
+        GameEvent::Shot { .. } => {
+            let laser_path = get_asset_path("laser.mp3");
+            let file = BufReader::new(File::open(&laser_path).unwrap());
+            let source = Decoder::new(file).unwrap();
+            laser_sink.append(source);
+        }
+        // The UFO hums in low as it arrives and chirps when it is shot down
+        GameEvent::UfoAppeared => play_tone(laser_sink, 220.0, 300),
+        GameEvent::UfoHit { .. } => {
+            play_tone(laser_sink, 880.0, 80);
+            play_tone(laser_sink, 1320.0, 160);
+        }
+        _ => {}
+    }
+}
+
+// Queue a plain tone, for sounds that have no asset file
+fn play_tone(sink: &Sink, frequency: f32, millis: u64) {
+    let tone = SineWave::new(frequency)
+        .take_duration(Duration::from_millis(millis))
+        .amplify(0.5);
+    sink.append(tone);
+}
+
 // Get the path to an asset file
 fn get_asset_path(file_name: &str) -> String {
     let exe_path = env::current_exe().unwrap_or_default();
@@ -655,8 +1282,214 @@ fn get_asset_path(file_name: &str) -> String {
     format!("audio/{}", file_name)
 }
 
+// Load a level file, exiting with a message if it is invalid
+fn load_level_file(path: &str) -> Vec<Wave> {
+    match levels::load_levels(path) {
+        Ok(waves) => waves,
+        Err(e) => {
+            eprintln!("Error in level file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Load a pattern file on top of the bundled patterns, or exit with an error
+fn load_pattern_file(path: &str) -> Patterns {
+    match patterns::load_patterns(path) {
+        Ok(patterns) => patterns,
+        Err(e) => {
+            eprintln!("Error in pattern file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The patterns the game fires: a file given with --patterns, the default pattern
+// file if there is one, or only the bundled patterns
+fn find_patterns(pattern_file: Option<&str>) -> Patterns {
+    match pattern_file {
+        Some(path) => load_pattern_file(path),
+        None if Path::new(DEFAULT_PATTERN_FILE).exists() => load_pattern_file(DEFAULT_PATTERN_FILE),
+        None => Patterns::bundled(),
+    }
+}
+
 // Main function to run the game
 fn main() -> io::Result<()> {
+    // Parse command line options
+    let mut level_file = None;
+    let mut pattern_file = None;
+    let mut edit_file = None;
+    let mut mode = GameMode::Arcade;
+    let mut net_role = None;
+    let mut versus = false;
+    let mut spectate = None;
+    let mut autopilot = false;
+    let mut stdio_env = false;
+    let mut difficulty = Difficulty::Normal;
+    let mut args = env::args().skip(1).peekable();
+    if args.next_if_eq("fuzz").is_some() {
+        return fuzz::run(args.collect());
+    }
+    if args.next_if_eq("simulate").is_some() {
+        return simulate::run(args.collect());
+    }
+    if args.next_if_eq("snapshots").is_some() {
+        return snapshot::run(args.collect());
+    }
+    if args.next_if_eq("serve").is_some() {
+        return serve::run(args.collect());
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--levels" => match args.next() {
+                Some(path) => level_file = Some(path),
+                None => {
+                    eprintln!("--levels needs a file name");
+                    std::process::exit(2);
+                }
+            },
+            "--patterns" => match args.next() {
+                Some(path) => pattern_file = Some(path),
+                None => {
+                    eprintln!("--patterns needs a file name");
+                    std::process::exit(2);
+                }
+            },
+            "--mode" => match args.next().as_deref().and_then(GameMode::from_name) {
+                Some(chosen) => mode = chosen,
+                None => {
+                    eprintln!("--mode needs 'arcade' or 'classic'");
+                    std::process::exit(2);
+                }
+            },
+            "--difficulty" => match args.next().as_deref().and_then(Difficulty::from_name) {
+                Some(chosen) => difficulty = chosen,
+                None => {
+                    eprintln!("--difficulty needs 'easy', 'normal' or 'hard'");
+                    std::process::exit(2);
+                }
+            },
+            "--host" => match args.next().and_then(|port| port.parse().ok()) {
+                Some(port) => net_role = Some(netplay::Role::Host(port)),
+                None => {
+                    eprintln!("--host needs a port number");
+                    std::process::exit(2);
+                }
+            },
+            "--join" => match args.next() {
+                Some(address) => net_role = Some(netplay::Role::Join(address)),
+                None => {
+                    eprintln!("--join needs an address, like 192.168.1.20:7777");
+                    std::process::exit(2);
+                }
+            },
+            "--versus" => versus = true,
+            "--autopilot" => autopilot = true,
+            "--stdio-env" => stdio_env = true,
+            "--spectate" => match args.next() {
+                Some(address) => spectate = Some(Endpoint::parse(&address)),
+                None => {
+                    eprintln!("--spectate needs a port, an address or unix:<path>");
+                    std::process::exit(2);
+                }
+            },
+            "--edit" => match args.next() {
+                Some(path) => edit_file = Some(path),
+                None => {
+                    eprintln!("--edit needs a file name");
+                    std::process::exit(2);
+                }
+            },
+            _ => {
+                eprintln!("Unknown option: {}", arg);
+                eprintln!(
+                    "Usage: space_invaders [--levels <file>] [--patterns <file>] [--mode <arcade|classic>] [--difficulty <easy|normal|hard>]"
+                );
+                eprintln!(
+                    "                      [--spectate <address>] [--autopilot] [--edit <file>]"
+                );
+                eprintln!(
+                    "       space_invaders [--host <port> [--versus] | --join <address>] [--levels <file>] [--patterns <file>] [--mode <arcade|classic>]"
+                );
+                eprintln!(
+                    "       space_invaders --stdio-env [--levels <file>] [--patterns <file>] [--mode <arcade|classic>]"
+                );
+                eprintln!(
+                    "       space_invaders fuzz [--seed <n>] [--ticks <n>] [--levels <file>] [--patterns <file>]"
+                );
+                eprintln!(
+                    "       space_invaders simulate [--games <n>] [--agent <autopilot|idle>] [--difficulty <easy|normal|hard>] [--format <csv|json>] ..."
+                );
+                eprintln!(
+                    "       space_invaders snapshots [--bless] [--dir <dir>] [<scenario> ...]"
+                );
+                eprintln!(
+                    "       space_invaders serve [--port <n>] [--levels <file>] [--patterns <file>] [--mode <arcade|classic>]"
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // The file being edited is the one played, so naming another is a mistake
+    if edit_file.is_some() && level_file.is_some() {
+        eprintln!("--edit and --levels both pick the level file, use one or the other");
+        std::process::exit(2);
+    }
+
+    // Load hand-authored waves. Without a level file the game uses random waves, and a file
+    // opened for editing may not exist yet, but a missing --levels file is an error
+    let required = edit_file.is_none() && level_file.is_some();
+    let level_path = edit_file
+        .clone()
+        .or(level_file)
+        .unwrap_or_else(|| DEFAULT_LEVEL_FILE.to_string());
+    let mut waves = if required || Path::new(&level_path).exists() {
+        load_level_file(&level_path)
+    } else {
+        Vec::new()
+    };
+
+    let patterns = find_patterns(pattern_file.as_deref());
+
+    // Agents in other processes play headless, without the terminal or sound
+    if stdio_env {
+        return stdio_env::run(waves, patterns, mode);
+    }
+
+    // Both players in a network game have to be at the keyboard, every key is sent
+    if autopilot && net_role.is_some() {
+        eprintln!("--autopilot can't fly in network games");
+        std::process::exit(2);
+    }
+    let mut autopilot = autopilot.then(Autopilot::default);
+
+    // Network games connect before the terminal goes into raw mode, so the wait
+    // for the other player can be printed
+    let session =
+        net_role.map(
+            |role| match netplay::connect(&role, &waves, &patterns, mode, versus) {
+                Ok(session) => session,
+                Err(e) => {
+                    eprintln!("Could not start the network game: {}", e);
+                    std::process::exit(1);
+                }
+            },
+        );
+
+    // Viewers can connect from the start, and are told how before the screen is taken over
+    let mut spectators = spectate.map(|endpoint| match Spectators::open(&endpoint) {
+        Ok(spectators) => {
+            println!("Spectators can watch with '{}'", endpoint.watch_command());
+            spectators
+        }
+        Err(e) => {
+            eprintln!("Could not open the spectator endpoint: {}", e);
+            std::process::exit(1);
+        }
+    });
+
     // Set up the terminal screen
     let mut screen = AlternateScreen::from(stdout().into_raw_mode()?);
     let (tx, rx) = mpsc::channel();
@@ -686,11 +1519,9 @@ fn main() -> io::Result<()> {
     // Spawn a thread to handle user input
     thread::spawn(move || {
         let stdin = io::stdin();
-        for key in stdin.keys() {
-            if let Ok(key) = key {
-                if tx.send(key).is_err() {
-                    return;
-                }
+        for key in stdin.keys().flatten() {
+            if tx.send(key).is_err() {
+                return;
             }
         }
     });
@@ -699,129 +1530,184 @@ fn main() -> io::Result<()> {
     let mut music_volume: f32 = 0.5; // Initial music volume
     let mut last_update_time = std::time::Instant::now();
 
+    // Network games skip the menus and end after one game
+    if let Some(session) = session {
+        return netplay::run(
+            &mut screen,
+            &rx,
+            &laser_sink,
+            session,
+            waves,
+            patterns,
+            spectators.as_mut(),
+        );
+    }
+
+    // Go straight to the level editor when asked to
+    if edit_file.is_some() {
+        let preview = Preview {
+            patterns: &patterns,
+            mode,
+            difficulty,
+        };
+        run_level_editor(
+            &mut screen,
+            &rx,
+            &laser_sink,
+            &level_path,
+            &mut waves,
+            &preview,
+        )?;
+        return Ok(());
+    }
+
     'main_loop: loop {
         // Display the start screen
         display_start_screen(&mut screen)?;
 
-        // Wait for the user to start the game, view tutorial, or quit
+        // Wait for the user to start the game, view tutorial, or quit. Left idle,
+        // the title gives way to the attract loop until a key is pressed
+        let player_count;
         loop {
-            if let Ok(key) = rx.recv() {
-                match key {
-                    Key::Char('s') | Key::Char('S') => break,
-                    Key::Char('t') | Key::Char('T') => {
-                        display_tutorial_screen(&mut screen)?;
-                        loop {
-                            if let Ok(key) = rx.recv() {
-                                if let Key::Char('b') | Key::Char('B') = key {
-                                    break;
-                                }
-                            }
+            let key = match rx.recv_timeout(attract::IDLE_DELAY) {
+                Ok(key) => key,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    attract::run(&mut screen, &rx, &waves, &patterns, mode)?;
+                    display_start_screen(&mut screen)?;
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break 'main_loop,
+            };
+            match key {
+                Key::Char('s') | Key::Char('S') => {
+                    player_count = 1;
+                    break;
+                }
+                Key::Char('2') => {
+                    player_count = 2;
+                    break;
+                }
+                Key::Char('t') | Key::Char('T') => {
+                    display_tutorial_screen(&mut screen)?;
+                    loop {
+                        if let Ok(Key::Char('b') | Key::Char('B')) = rx.recv() {
+                            break;
                         }
-                        display_start_screen(&mut screen)?;
                     }
-                    Key::Char('o') | Key::Char('O') => {
-                        display_option_screen(&mut screen)?;
-                        loop {
-                            if let Ok(key) = rx.recv() {
-                                match key {
-                                    Key::Char('b') | Key::Char('B') => break, // Go back to start screen
-
-                                    // Increase laser volume
-                                    Key::Char('+') => {
-                                        if laser_volume < 1.0 {
-                                            laser_volume += 0.1;
-                                            laser_sink.set_volume(laser_volume);
-                                            // Adjust laser volume
-                                            let now = std::time::Instant::now();
-                                            if now.duration_since(last_update_time)
-                                                >= Duration::new(2, 0)
-                                            {
-                                                clear_line();
-                                                print_laser_volume(laser_volume);
-                                                last_update_time = now;
-                                            } else {
-                                                // Clear previous line and print the new value
-                                                clear_line();
-                                                print_laser_volume(laser_volume);
-                                                last_update_time = now;
-                                            }
-                                        }
-                                    }
+                    display_start_screen(&mut screen)?;
+                }
+                Key::Char('o') | Key::Char('O') => {
+                    display_option_screen(&mut screen, mode)?;
+                    loop {
+                        if let Ok(key) = rx.recv() {
+                            match key {
+                                Key::Char('b') | Key::Char('B') => break, // Go back to start screen
+
+                                // Switch between arcade and classic enemies
+                                Key::Char('m') | Key::Char('M') => {
+                                    mode = mode.next();
+                                    display_option_screen(&mut screen, mode)?;
+                                }
 
-                                    // Decrease laser volume
-                                    Key::Char('-') => {
-                                        if laser_volume > 0.0 {
-                                            laser_volume -= 0.1;
-                                            laser_sink.set_volume(laser_volume);
-                                            // Adjust laser volume
-                                            let now = std::time::Instant::now();
-                                            if now.duration_since(last_update_time)
-                                                >= Duration::new(2, 0)
-                                            {
-                                                clear_line();
-                                                print_laser_volume(laser_volume);
-                                                last_update_time = now;
-                                            } else {
-                                                // Clear previous line and print the new value
-                                                clear_line();
-                                                print_laser_volume(laser_volume);
-                                                last_update_time = now;
-                                            }
-                                        }
+                                // Increase laser volume
+                                Key::Char('+') if laser_volume < 1.0 => {
+                                    laser_volume += 0.1;
+                                    laser_sink.set_volume(laser_volume);
+                                    // Adjust laser volume
+                                    let now = std::time::Instant::now();
+                                    if now.duration_since(last_update_time) >= Duration::new(2, 0) {
+                                        clear_line();
+                                        print_laser_volume(laser_volume);
+                                        last_update_time = now;
+                                    } else {
+                                        // Clear previous line and print the new value
+                                        clear_line();
+                                        print_laser_volume(laser_volume);
+                                        last_update_time = now;
                                     }
+                                }
 
-                                    // Increase background music volume
-                                    Key::Char(']') => {
-                                        if music_volume < 1.0 {
-                                            music_volume += 0.1;
-                                            sink.set_volume(music_volume); // Adjust music volume
-
-                                            let now = std::time::Instant::now();
-                                            if now.duration_since(last_update_time)
-                                                >= Duration::new(2, 0)
-                                            {
-                                                clear_line();
-                                                print_music_volume(music_volume);
-                                                last_update_time = now;
-                                            } else {
-                                                // Clear previous line and print the new value
-                                                clear_line();
-                                                print_music_volume(music_volume);
-                                                last_update_time = now;
-                                            }
-                                        }
+                                // Decrease laser volume
+                                Key::Char('-') if laser_volume > 0.0 => {
+                                    laser_volume -= 0.1;
+                                    laser_sink.set_volume(laser_volume);
+                                    // Adjust laser volume
+                                    let now = std::time::Instant::now();
+                                    if now.duration_since(last_update_time) >= Duration::new(2, 0) {
+                                        clear_line();
+                                        print_laser_volume(laser_volume);
+                                        last_update_time = now;
+                                    } else {
+                                        // Clear previous line and print the new value
+                                        clear_line();
+                                        print_laser_volume(laser_volume);
+                                        last_update_time = now;
                                     }
+                                }
 
-                                    // Decrease background music volume
-                                    Key::Char('[') => {
-                                        if music_volume > 0.0 {
-                                            music_volume -= 0.1;
-                                            sink.set_volume(music_volume); // Adjust music volume
-                                            let now = std::time::Instant::now();
-                                            if now.duration_since(last_update_time)
-                                                >= Duration::new(2, 0)
-                                            {
-                                                clear_line();
-                                                print_music_volume(music_volume);
-                                                last_update_time = now;
-                                            } else {
-                                                // Clear previous line and print the new value
-                                                clear_line();
-                                                print_music_volume(music_volume);
-                                                last_update_time = now;
-                                            }
-                                        }
+                                // Increase background music volume
+                                Key::Char(']') if music_volume < 1.0 => {
+                                    music_volume += 0.1;
+                                    sink.set_volume(music_volume); // Adjust music volume
+
+                                    let now = std::time::Instant::now();
+                                    if now.duration_since(last_update_time) >= Duration::new(2, 0) {
+                                        clear_line();
+                                        print_music_volume(music_volume);
+                                        last_update_time = now;
+                                    } else {
+                                        // Clear previous line and print the new value
+                                        clear_line();
+                                        print_music_volume(music_volume);
+                                        last_update_time = now;
                                     }
+                                }
 
-                                    _ => {}
+                                // Decrease background music volume
+                                Key::Char('[') if music_volume > 0.0 => {
+                                    music_volume -= 0.1;
+                                    sink.set_volume(music_volume); // Adjust music volume
+                                    let now = std::time::Instant::now();
+                                    if now.duration_since(last_update_time) >= Duration::new(2, 0) {
+                                        clear_line();
+                                        print_music_volume(music_volume);
+                                        last_update_time = now;
+                                    } else {
+                                        // Clear previous line and print the new value
+                                        clear_line();
+                                        print_music_volume(music_volume);
+                                        last_update_time = now;
+                                    }
                                 }
+
+                                _ => {}
                             }
                         }
-                        display_start_screen(&mut screen)?;
                     }
-                    Key::Char('q') | Key::Char('Q') => break 'main_loop,
-                    _ => {}
+                    display_start_screen(&mut screen)?;
                 }
+                Key::Char('e') | Key::Char('E') => {
+                    // The mode picked on the options screen so far
+                    let preview = Preview {
+                        patterns: &patterns,
+                        mode,
+                        difficulty,
+                    };
+                    let quit = run_level_editor(
+                        &mut screen,
+                        &rx,
+                        &laser_sink,
+                        &level_path,
+                        &mut waves,
+                        &preview,
+                    )?;
+                    if quit {
+                        break 'main_loop;
+                    }
+                    display_start_screen(&mut screen)?;
+                }
+                Key::Char('q') | Key::Char('Q') => break 'main_loop,
+                _ => {}
             }
         }
         fn clear_line() {
@@ -842,48 +1728,38 @@ fn main() -> io::Result<()> {
 
         // Initialize the game
         let mut game = Game::new();
-        game.enemies = game.create_enemies();
-        let mut last_update = Instant::now();
+        game.set_player_count(player_count);
+        game.waves = waves.clone();
+        game.patterns = patterns.clone();
+        game.mode = mode;
+        game.difficulty = difficulty;
+        game.start_level();
 
         // Main game loop
-        'game_loop: loop {
-            // Update game state every 50ms
-            if last_update.elapsed() >= Duration::from_millis(50) {
-                game.update();
-                write!(screen, "{}{}", termion::clear::All, game.render())?;
-                if game.paused {
-                    display_pause_screen(&mut screen)?;
-                }
-                screen.flush()?;
-                last_update = Instant::now();
-
-                // Check if the game is over
-                if game.is_game_over() {
-                    game.save_high_score();
-                    break 'game_loop;
-                }
-            }
-
-            // Handle user input
-            if let Ok(key) = rx.try_recv() {
-                match key {
-                    Key::Ctrl('c') => break 'main_loop,
-                    key => game.handle_input(key, &laser_sink),
-                }
-            }
-
-            // Small sleep to prevent CPU hogging
-            thread::sleep(Duration::from_millis(10));
+        if let GameExit::Quit = run_game(
+            &mut screen,
+            &rx,
+            Some(&laser_sink),
+            &mut game,
+            false,
+            spectators.as_mut(),
+            autopilot.as_mut().map(|pilot| pilot as &mut dyn Agent),
+        )? {
+            break 'main_loop;
         }
+        let (leaderboard, place) = game.save_score();
 
         // Display game over screen
         let time_survived = game.start_time.elapsed();
+        let scores: Vec<u32> = game.players.iter().map(|player| player.score).collect();
         display_game_over_screen(
             &mut screen,
-            game.score,
+            &scores,
             game.level,
-            game.high_score,
+            &leaderboard.top(game.category()),
+            place,
             time_survived,
+            &game.stats,
         )?;
 
         // Wait for the user to restart or quit
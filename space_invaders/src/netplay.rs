@@ -0,0 +1,635 @@
+use rand::Rng;
+use rodio::Sink;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use termion::color;
+use termion::event::Key;
+use termion::screen::AlternateScreen;
+
+use crate::bullets::Bullet;
+use crate::display_game_over_screen::display_game_over_screen;
+use crate::display_pause_screen::display_pause_screen;
+use crate::game_mode::GameMode;
+use crate::leaderboard::{Leaderboard, ENTRIES};
+use crate::levels::{self, Wave};
+use crate::patterns::Patterns;
+use crate::player::{self, Action};
+use crate::powerups::Powerup;
+use crate::spectate::Spectators;
+use crate::{handle_events, Game, TICK_DURATION};
+
+// Two players on different machines play one game in lockstep. Only inputs cross
+// the network: both sides run the whole game, and since a game plays out the same
+// way given its seed and the keys pressed on every tick, they stay in step. Keys
+// are sent INPUT_DELAY ticks ahead so they usually arrive before they are needed,
+// and the two sides swap checksums of the game now and then to catch a desync.
+// The host is player one and the player who joins is player two
+//
+// The protocol is line based text:
+//   hello <version> <seed> <mode> <versus> <setup>   host to guest, starts the game
+//   hello <version> <setup>                          the guest's reply
+//   input <tick> <keys>                              a player's keys for a tick
+//   sum <tick> <checksum>                            the game after a tick
+//   bye                                              the player quit
+//
+// Keys are sent as l, r and f for left, right and fire and p for pause, or '-' for
+// no keys. <setup> is a checksum of the levels and patterns, which both players
+// need the same files for
+
+const VERSION: u32 = 2;
+pub const DEFAULT_PORT: u16 = 7777;
+// Ticks between a key being pressed and the tick it is played on
+const INPUT_DELAY: u64 = 3;
+// Ticks between game checksums
+const CHECKSUM_INTERVAL: u64 = 20;
+// How long to wait for the other player before giving up
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Which side of the connection this player is on
+pub enum Role {
+    Host(u16),
+    Join(String),
+}
+
+// A connected game, agreed on in the handshake
+pub struct Session {
+    stream: TcpStream,
+    local: usize, // The player on this machine
+    seed: u64,
+    mode: GameMode,
+    versus: bool,
+}
+
+// How a network game ended
+enum Outcome {
+    GameOver,
+    Quit,
+    Disconnected(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Message {
+    Input(u64, String),
+    Sum(u64, u64),
+    Bye,
+    Closed, // The connection dropped, or sent something that isn't the protocol
+}
+
+fn protocol_error(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Connect to the other player and agree on the game. Runs before the terminal is
+// in raw mode, so it can print progress
+pub fn connect(
+    role: &Role,
+    waves: &[Wave],
+    patterns: &Patterns,
+    mode: GameMode,
+    versus: bool,
+) -> io::Result<Session> {
+    let setup = setup_checksum(waves, patterns);
+    match role {
+        Role::Host(port) => {
+            let listener = TcpListener::bind(("0.0.0.0", *port))?;
+            println!("Waiting for a player to join on port {}...", port);
+            let (stream, address) = listener.accept()?;
+            println!("{} joined", address);
+            stream.set_nodelay(true)?;
+            let seed = rand::random();
+            let mut writer = stream.try_clone()?;
+            writeln!(
+                writer,
+                "hello {} {} {} {} {}",
+                VERSION,
+                seed,
+                mode.name(),
+                versus as u8,
+                setup
+            )?;
+            let reply = read_line(&stream)?;
+            match reply.split_whitespace().collect::<Vec<_>>()[..] {
+                ["hello", version, their_setup] => {
+                    check_handshake(version, their_setup, setup)?;
+                }
+                _ => return Err(protocol_error("the other player did not say hello")),
+            }
+            Ok(Session {
+                stream,
+                local: 0,
+                seed,
+                mode,
+                versus,
+            })
+        }
+        Role::Join(address) => {
+            // The port can be left off when the host uses the default one
+            let address = if address.contains(':') {
+                address.clone()
+            } else {
+                format!("{}:{}", address, DEFAULT_PORT)
+            };
+            println!("Joining {}...", address);
+            let stream = TcpStream::connect(address.as_str())?;
+            stream.set_nodelay(true)?;
+            let hello = read_line(&stream)?;
+            let words: Vec<&str> = hello.split_whitespace().collect();
+            let ["hello", version, seed, mode, versus, their_setup] = words[..] else {
+                return Err(protocol_error("the host did not say hello"));
+            };
+            let mut writer = stream.try_clone()?;
+            writeln!(writer, "hello {} {}", VERSION, setup)?;
+            check_handshake(version, their_setup, setup)?;
+            // The host picks the seed, mode and versus for both players
+            let seed = seed
+                .parse()
+                .map_err(|_| protocol_error("the host sent a bad seed"))?;
+            let mode = GameMode::from_name(mode)
+                .ok_or_else(|| protocol_error("the host sent an unknown game mode"))?;
+            Ok(Session {
+                stream,
+                local: 1,
+                seed,
+                mode,
+                versus: versus == "1",
+            })
+        }
+    }
+}
+
+// Read one handshake line a byte at a time, so nothing sent after it is buffered
+// and lost. Gives up if the other player says nothing for too long
+fn read_line(mut stream: &TcpStream) -> io::Result<String> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut line = Vec::new();
+    let mut byte = [0];
+    while stream.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    stream.set_read_timeout(None)?;
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+fn check_handshake(version: &str, their_setup: &str, setup: u64) -> io::Result<()> {
+    if version != VERSION.to_string() {
+        return Err(protocol_error(format!(
+            "the other player runs protocol version {}, this is version {}",
+            version, VERSION
+        )));
+    }
+    if their_setup != setup.to_string() {
+        return Err(protocol_error(
+            "the other player has different level or pattern files",
+        ));
+    }
+    Ok(())
+}
+
+// Both players need the same levels and patterns to play the same game
+fn setup_checksum(waves: &[Wave], patterns: &Patterns) -> u64 {
+    let mut hasher = Fnv::default();
+    levels::format_levels(waves).hash(&mut hasher);
+    format!("{:?}", patterns).hash(&mut hasher);
+    hasher.finish()
+}
+
+// A checksum of everything that decides how the game plays out from here
+fn checksum(game: &Game) -> u64 {
+    let mut hasher = Fnv::default();
+    (game.tick, game.level, game.paused).hash(&mut hasher);
+    // The next number the generator hands out stands in for its state
+    game.rng.clone().gen::<u64>().hash(&mut hasher);
+    for player in &game.players {
+        (player.x, player.lives, player.score, player.powerup_timer).hash(&mut hasher);
+        player.powerup_active.map(Powerup::glyph).hash(&mut hasher);
+    }
+    for enemy in &game.enemies {
+        (enemy.x, enemy.y, enemy.health, enemy.shoot_timer).hash(&mut hasher);
+    }
+    for bullet in &game.bullets {
+        hash_bullet(bullet, &mut hasher);
+    }
+    for (at, bullet) in &game.pending_bullets {
+        at.hash(&mut hasher);
+        hash_bullet(bullet, &mut hasher);
+    }
+    for &(x, y, powerup) in &game.powerups {
+        (x, y, powerup.glyph()).hash(&mut hasher);
+    }
+    if let Some(boss) = &game.boss {
+        (boss.x, boss.y).hash(&mut hasher);
+        for part in &boss.parts {
+            part.health.hash(&mut hasher);
+        }
+    }
+    game.bunkers.cells().for_each(|cell| cell.hash(&mut hasher));
+    game.ufo.as_ref().map(|ufo| ufo.x).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Where a bullet is, where it is going and whose it is
+fn hash_bullet(bullet: &Bullet, hasher: &mut Fnv) {
+    let motion = [
+        bullet.x, bullet.y, bullet.vx, bullet.vy, bullet.ax, bullet.ay,
+    ];
+    motion.map(f32::to_bits).hash(hasher);
+    (bullet.is_enemy, bullet.owner).hash(hasher);
+}
+
+// 64-bit FNV-1a. Checksums cross the network, so unlike DefaultHasher it has to give
+// the same answer on every machine and Rust release. Numbers are hashed little
+// endian and usize as 64 bits for the same reason
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+// Play a connected game to the end, then show how it went
+pub fn run(
+    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
+    rx: &mpsc::Receiver<Key>,
+    laser_sink: &Sink,
+    session: Session,
+    waves: Vec<Wave>,
+    patterns: Patterns,
+    spectators: Option<&mut Spectators>,
+) -> io::Result<()> {
+    let mut game = Game::with_seed(session.seed);
+    game.set_player_count(2);
+    game.mode = session.mode;
+    game.versus = session.versus;
+    game.waves = waves;
+    game.patterns = patterns;
+    game.start_level();
+
+    let outcome = play(screen, rx, laser_sink, &session, &mut game, spectators)?;
+    if let Outcome::Quit = outcome {
+        return Ok(());
+    }
+
+    // Versus games don't go on the leaderboard, co-op games do
+    let (leaderboard, place) = if game.versus {
+        (Leaderboard::load(), None)
+    } else {
+        game.save_score()
+    };
+    let scores: Vec<u32> = game.players.iter().map(|player| player.score).collect();
+    display_game_over_screen(
+        screen,
+        &scores,
+        game.level,
+        &leaderboard.top(game.category()),
+        place,
+        game.start_time.elapsed(),
+        &game.stats,
+    )?;
+    let result = match outcome {
+        Outcome::Disconnected(reason) => format!("Game stopped: {}", reason),
+        _ if game.versus => match scores[0].cmp(&scores[1]) {
+            Ordering::Greater => "Player 1 wins!".to_string(),
+            Ordering::Less => "Player 2 wins!".to_string(),
+            Ordering::Equal => "It's a draw!".to_string(),
+        },
+        _ => String::new(),
+    };
+    write!(
+        screen,
+        "{}{}{}",
+        termion::cursor::Goto(4, 7),
+        color::Fg(color::LightYellow),
+        result
+    )?;
+    // There is no playing again over the network, so replace the usual prompts
+    let prompt_row = 17 + ENTRIES as u16;
+    write!(
+        screen,
+        "{}{}{}{}",
+        termion::cursor::Goto(1, prompt_row + 1),
+        termion::clear::CurrentLine,
+        termion::cursor::Goto(1, prompt_row),
+        termion::clear::CurrentLine
+    )?;
+    write!(
+        screen,
+        "{}{}Press any key to quit{}",
+        termion::cursor::Goto(4, prompt_row),
+        color::Fg(color::Red),
+        color::Fg(color::Reset)
+    )?;
+    screen.flush()?;
+    let _ = rx.recv();
+    Ok(())
+}
+
+// The lockstep loop. A tick is only played once both players' keys for it are in
+fn play(
+    screen: &mut AlternateScreen<termion::raw::RawTerminal<std::io::Stdout>>,
+    rx: &mpsc::Receiver<Key>,
+    laser_sink: &Sink,
+    session: &Session,
+    game: &mut Game,
+    mut spectators: Option<&mut Spectators>,
+) -> io::Result<Outcome> {
+    let mut writer = session.stream.try_clone()?;
+    let messages = listen(session.stream.try_clone()?);
+
+    // Keys for each tick, by player. Nobody presses anything during the first
+    // INPUT_DELAY ticks
+    let mut inputs: [BTreeMap<u64, String>; 2] = Default::default();
+    for keys in &mut inputs {
+        keys.extend((0..INPUT_DELAY).map(|tick| (tick, String::new())));
+    }
+    let mut sums: [BTreeMap<u64, u64>; 2] = Default::default();
+    let remote = 1 - session.local;
+    let mut pressed = String::new(); // Keys pressed since the last tick was sent
+    let mut tick = 0;
+    let mut last_update = Instant::now();
+    let mut last_heard = Instant::now();
+
+    loop {
+        for key in rx.try_iter() {
+            if key == Key::Ctrl('c') {
+                let _ = writeln!(writer, "bye");
+                return Ok(Outcome::Quit);
+            }
+            if let Some(token) = key_token(key) {
+                pressed.push(token);
+            }
+        }
+
+        for message in messages.try_iter() {
+            last_heard = Instant::now();
+            match message {
+                Message::Input(at, keys) => {
+                    inputs[remote].insert(at, keys);
+                }
+                Message::Sum(at, sum) => {
+                    sums[remote].insert(at, sum);
+                }
+                Message::Bye => {
+                    return Ok(Outcome::Disconnected("the other player left".to_string()))
+                }
+                Message::Closed => {
+                    return Ok(Outcome::Disconnected("the connection was lost".to_string()))
+                }
+            }
+        }
+        if let Some(at) = desync(&mut sums) {
+            let _ = writeln!(writer, "bye");
+            return Ok(Outcome::Disconnected(format!(
+                "the games went out of sync at tick {}",
+                at
+            )));
+        }
+
+        if last_update.elapsed() >= TICK_DURATION && inputs[remote].contains_key(&tick) {
+            // Send this tick's keys ahead for the tick they will be played on
+            let keys = std::mem::take(&mut pressed);
+            let wire = if keys.is_empty() { "-" } else { keys.as_str() };
+            if writeln!(writer, "input {} {}", tick + INPUT_DELAY, wire).is_err() {
+                return Ok(Outcome::Disconnected("the connection was lost".to_string()));
+            }
+            inputs[session.local].insert(tick + INPUT_DELAY, keys);
+
+            // Both sides play the players' keys in the same order
+            for (index, keys) in inputs.iter_mut().enumerate() {
+                for token in keys.remove(&tick).unwrap_or_default().chars() {
+                    apply(game, index, token);
+                }
+            }
+            game.update();
+            let frame = game.render();
+            if let Some(spectators) = spectators.as_deref_mut() {
+                spectators.record(&game.events);
+                spectators.show(&frame, game.paused);
+            }
+            handle_events(game, Some(laser_sink));
+            write!(screen, "{}{}", termion::clear::All, frame)?;
+            if game.paused {
+                display_pause_screen(screen)?;
+            }
+            screen.flush()?;
+
+            if tick % CHECKSUM_INTERVAL == 0 {
+                let sum = checksum(game);
+                let _ = writeln!(writer, "sum {} {}", tick, sum);
+                sums[session.local].insert(tick, sum);
+            }
+            tick += 1;
+            last_update = Instant::now();
+
+            if game.is_game_over() {
+                let _ = writeln!(writer, "bye");
+                return Ok(Outcome::GameOver);
+            }
+        } else if last_heard.elapsed() >= TIMEOUT {
+            return Ok(Outcome::Disconnected(
+                "the other player stopped responding".to_string(),
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(2));
+    }
+}
+
+// Read messages from the other player on a thread of their own
+fn listen(stream: TcpStream) -> mpsc::Receiver<Message> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let message = line.ok().and_then(|line| parse_message(&line));
+            let done = matches!(message, None | Some(Message::Bye));
+            if tx.send(message.unwrap_or(Message::Closed)).is_err() || done {
+                return;
+            }
+        }
+        let _ = tx.send(Message::Closed);
+    });
+    rx
+}
+
+fn parse_message(line: &str) -> Option<Message> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words[..] {
+        ["input", tick, keys] => {
+            let keys = if keys == "-" { "" } else { keys };
+            if !keys.chars().all(|c| "lrfp".contains(c)) {
+                return None;
+            }
+            Some(Message::Input(tick.parse().ok()?, keys.to_string()))
+        }
+        ["sum", tick, sum] => Some(Message::Sum(tick.parse().ok()?, sum.parse().ok()?)),
+        ["bye"] => Some(Message::Bye),
+        _ => None,
+    }
+}
+
+// The first tick both sides have a checksum for that differs. Ticks that match
+// are forgotten
+fn desync(sums: &mut [BTreeMap<u64, u64>; 2]) -> Option<u64> {
+    let [local, remote] = sums;
+    let both: Vec<u64> = local
+        .keys()
+        .filter(|tick| remote.contains_key(tick))
+        .copied()
+        .collect();
+    both.into_iter()
+        .find(|tick| local.remove(tick) != remote.remove(tick))
+}
+
+// Either player's keys steer this machine's ship, since the other ship is remote
+fn key_token(key: Key) -> Option<char> {
+    match (key, player::action_for(key)) {
+        (Key::Char('p') | Key::Char('P'), _) => Some('p'),
+        (_, Some((_, Action::Left))) => Some('l'),
+        (_, Some((_, Action::Right))) => Some('r'),
+        (_, Some((_, Action::Fire))) => Some('f'),
+        _ => None,
+    }
+}
+
+fn apply(game: &mut Game, index: usize, token: char) {
+    match token {
+        'p' => game.paused = !game.paused,
+        'l' => game.handle_action(index, Action::Left),
+        'r' => game.handle_action(index, Action::Right),
+        _ => game.handle_action(index, Action::Fire),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv_matches_the_reference_values() {
+        let mut hasher = Fnv::default();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+        let mut hasher = Fnv::default();
+        hasher.write(b"foobar");
+        assert_eq!(hasher.finish(), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn checksums_follow_the_random_numbers() {
+        let mut game = Game::with_seed(7);
+        let before = checksum(&game);
+        assert_eq!(before, checksum(&Game::with_seed(7)));
+        game.rng.gen::<u64>();
+        assert_ne!(before, checksum(&game));
+        assert_ne!(before, checksum(&Game::with_seed(8)));
+    }
+
+    #[test]
+    fn checksums_cover_powerups_and_bullets_in_flight() {
+        let game = || {
+            let mut game = Game::with_seed(7);
+            game.bullets.push(Bullet::new(10, 10, 0.0, 1.0, true));
+            game
+        };
+        let before = checksum(&game());
+        let changes: [fn(&mut Game); 6] = [
+            |game| game.powerups.push((5, 0, Powerup::Shield)),
+            |game| game.players[0].powerup_active = Some(Powerup::RapidFire),
+            |game| {
+                game.pending_bullets
+                    .push((3, Bullet::new(1, 1, 0.0, 1.0, true)))
+            },
+            |game| game.bullets[0].vx = 0.5,
+            |game| game.bullets[0].ay = 0.05,
+            |game| game.bullets[0].is_enemy = false,
+        ];
+        for (i, change) in changes.iter().enumerate() {
+            let mut game = game();
+            change(&mut game);
+            assert_ne!(checksum(&game), before, "change {}", i);
+        }
+    }
+
+    #[test]
+    fn messages_parse() {
+        assert_eq!(
+            parse_message("input 12 lf"),
+            Some(Message::Input(12, "lf".to_string()))
+        );
+        assert_eq!(
+            parse_message("input 3 -"),
+            Some(Message::Input(3, String::new()))
+        );
+        assert_eq!(parse_message("sum 40 123"), Some(Message::Sum(40, 123)));
+        assert_eq!(parse_message("bye"), Some(Message::Bye));
+    }
+
+    #[test]
+    fn garbage_is_not_a_message() {
+        for line in [
+            "",
+            "input 12 lx",
+            "input -1 l",
+            "input 12",
+            "sum 40",
+            "sum forty 1",
+            "bye now",
+            "hello 2 3",
+        ] {
+            assert_eq!(parse_message(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn desync_finds_the_first_mismatch() {
+        let mut sums = [
+            BTreeMap::from([(20, 1), (40, 2), (60, 3), (80, 4)]),
+            BTreeMap::from([(20, 1), (40, 9), (60, 8)]),
+        ];
+        assert_eq!(desync(&mut sums), Some(40));
+
+        let mut sums = [
+            BTreeMap::from([(20, 1), (40, 2), (60, 3)]),
+            BTreeMap::from([(20, 1), (40, 2)]),
+        ];
+        assert_eq!(desync(&mut sums), None);
+        // Matching ticks are forgotten, the one only this side has is kept
+        assert_eq!(sums[0].keys().collect::<Vec<_>>(), [&60]);
+        assert!(sums[1].is_empty());
+    }
+}
@@ -0,0 +1,560 @@
+use std::fmt;
+use std::fs;
+use std::ops::RangeInclusive;
+
+use crate::bullets::Bullet;
+use crate::collision::Cell;
+use crate::WIDTH;
+
+// Enemies and bosses fire bullet patterns by name. Patterns are written in a small
+// line based language, described at the top of patterns.txt, and run by `fire`.
+// The game ships with patterns.txt built in, and a pattern file loaded on top of it
+// replaces the patterns it names and adds new ones
+
+const BUNDLED: &str = include_str!("../patterns.txt");
+
+// Options every bullet step takes after its arguments
+const OPTIONS: [&str; 2] = ["speed", "gravity"];
+// Limits that keep one pattern from flooding the playfield
+const MAX_REPEAT: u32 = 50;
+const MAX_BULLETS: u64 = 100;
+
+// How the bullets of one step are laid out. Angles are in degrees, 0 is straight
+// down and 90 is to the right
+#[derive(Clone, Debug)]
+enum Shape {
+    Shot(f32),
+    Fan { count: u32, arc: f32 },
+    Ring(u32),
+    Aimed { count: u32, arc: f32 },
+    Wall { width: i32, gap: i32 },
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Bullets {
+        shape: Shape,
+        speed: f32,
+        gravity: f32,
+    },
+    Wait(u32),
+    Repeat {
+        times: u32,
+        turn: f32,
+        steps: Vec<Step>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub name: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Patterns {
+    patterns: Vec<Pattern>,
+}
+
+// A problem found while loading a pattern file
+#[derive(Debug)]
+pub struct PatternError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> PatternError {
+    PatternError {
+        line,
+        message: message.into(),
+    }
+}
+
+impl Patterns {
+    // The patterns built into the game
+    pub fn bundled() -> Self {
+        Patterns {
+            patterns: parse_patterns(BUNDLED).expect("the bundled patterns.txt is valid"),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Pattern> {
+        self.patterns.iter().find(|pattern| pattern.name == name)
+    }
+
+    // Add patterns, replacing any with the same name
+    fn extend(&mut self, patterns: Vec<Pattern>) {
+        for pattern in patterns {
+            self.patterns
+                .retain(|existing| existing.name != pattern.name);
+            self.patterns.push(pattern);
+        }
+    }
+
+    // Bullets a pattern fires from `origin` at the player at `target`, each with the
+    // number of ticks to hold it back for. Unknown patterns fire nothing
+    pub fn fire(&self, name: &str, origin: Cell, target: Cell) -> Vec<(u32, Bullet)> {
+        let mut bullets = Vec::new();
+        if let Some(pattern) = self.get(name) {
+            let (dx, dy) = (target.0 - origin.0, target.1 - origin.1);
+            let aim = (dx as f32).atan2(dy as f32).to_degrees();
+            let mut delay = 0;
+            run(&pattern.steps, origin, aim, 0.0, &mut delay, &mut bullets);
+        }
+        bullets
+    }
+}
+
+// Run steps, turned `turn` degrees. `delay` counts the ticks waited so far
+fn run(
+    steps: &[Step],
+    origin: Cell,
+    aim: f32,
+    turn: f32,
+    delay: &mut u32,
+    bullets: &mut Vec<(u32, Bullet)>,
+) {
+    for step in steps {
+        match step {
+            Step::Wait(ticks) => *delay += ticks,
+            Step::Repeat {
+                times,
+                turn: step_turn,
+                steps,
+            } => {
+                for i in 0..*times {
+                    let turn = turn + step_turn * i as f32;
+                    run(steps, origin, aim, turn, delay, bullets);
+                }
+            }
+            Step::Bullets {
+                shape,
+                speed,
+                gravity,
+            } => {
+                let bullet = |x: i32, angle: f32| {
+                    let angle = (angle + turn).to_radians();
+                    let (vx, vy) = (angle.sin() * speed, angle.cos() * speed);
+                    let bullet = Bullet::new(x, origin.1, vx, vy, true);
+                    (*delay, bullet.with_acceleration(0.0, *gravity))
+                };
+                match *shape {
+                    Shape::Shot(angle) => bullets.push(bullet(origin.0, angle)),
+                    Shape::Fan { count, arc } => {
+                        bullets.extend(spread(count, arc).map(|angle| bullet(origin.0, angle)))
+                    }
+                    Shape::Ring(count) => bullets.extend(
+                        (0..count).map(|i| bullet(origin.0, 360.0 * i as f32 / count as f32)),
+                    ),
+                    Shape::Aimed { count, arc } => bullets
+                        .extend(spread(count, arc).map(|angle| bullet(origin.0, aim + angle))),
+                    Shape::Wall { width, gap } => bullets.extend(
+                        (0..width)
+                            .step_by(gap as usize + 1)
+                            .map(|i| bullet(origin.0 + i - (width - 1) / 2, 0.0)),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+// Angles of `count` bullets spread evenly over an arc centered on 0
+fn spread(count: u32, arc: f32) -> impl Iterator<Item = f32> {
+    (0..count).map(move |i| {
+        if count == 1 {
+            0.0
+        } else {
+            -arc / 2.0 + arc * i as f32 / (count - 1) as f32
+        }
+    })
+}
+
+// Load a pattern file on top of the bundled patterns
+pub fn load_patterns(path: &str) -> Result<Patterns, PatternError> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| error(0, format!("could not read {}: {}", path, e)))?;
+    let mut patterns = Patterns::bundled();
+    patterns.extend(parse_patterns(&source)?);
+    Ok(patterns)
+}
+
+// Parse a pattern file. The format is line based and described at the top of
+// patterns.txt, lines starting with '#' are comments
+pub fn parse_patterns(source: &str) -> Result<Vec<Pattern>, PatternError> {
+    let mut patterns: Vec<Pattern> = Vec::new();
+    let mut pattern_line = 0;
+    // Repeats that haven't reached their 'end' yet: their line, count, turn and steps
+    let mut open: Vec<(usize, u32, f32, Vec<Step>)> = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (directive, args) = (words[0], &words[1..]);
+
+        if directive == "pattern" {
+            check_pattern(patterns.last(), pattern_line, &open)?;
+            let name = match args {
+                [name] => name.to_string(),
+                _ => return Err(error(line_number, "expected 'pattern <name>'")),
+            };
+            if patterns.iter().any(|pattern| pattern.name == name) {
+                return Err(error(
+                    line_number,
+                    format!("pattern '{}' is defined twice", name),
+                ));
+            }
+            patterns.push(Pattern {
+                name,
+                steps: Vec::new(),
+            });
+            pattern_line = line_number;
+            continue;
+        }
+
+        if patterns.is_empty() {
+            return Err(error(
+                line_number,
+                format!("'{}' must come after a 'pattern' line", directive),
+            ));
+        }
+
+        let step = match directive {
+            "wait" => match args {
+                [ticks] => Step::Wait(parse_count(ticks, "tick count", line_number)?),
+                _ => return Err(error(line_number, "expected 'wait <ticks>'")),
+            },
+            "repeat" => {
+                let (times, turn) = match args {
+                    [times] => (times, "0"),
+                    [times, turn] => (times, *turn),
+                    _ => return Err(error(line_number, "expected 'repeat <n> [<turn>]'")),
+                };
+                let times = parse_count(times, "repeat count", line_number)?;
+                if times > MAX_REPEAT {
+                    return Err(error(
+                        line_number,
+                        format!("repeat count must be at most {}", MAX_REPEAT),
+                    ));
+                }
+                let turn = parse_float(turn, "turn", line_number)?;
+                open.push((line_number, times, turn, Vec::new()));
+                continue;
+            }
+            "end" => {
+                if !args.is_empty() {
+                    return Err(error(line_number, "'end' takes no arguments"));
+                }
+                match open.pop() {
+                    Some((_, _, _, steps)) if steps.is_empty() => {
+                        return Err(error(line_number, "repeat has no steps"))
+                    }
+                    Some((_, times, turn, steps)) => Step::Repeat { times, turn, steps },
+                    None => return Err(error(line_number, "'end' without a 'repeat'")),
+                }
+            }
+            "shot" | "fan" | "ring" | "aimed" | "wall" => {
+                parse_bullets(directive, args, line_number)?
+            }
+            _ => {
+                return Err(error(
+                    line_number,
+                    format!("unknown directive '{}'", directive),
+                ))
+            }
+        };
+        match open.last_mut() {
+            Some((_, _, _, steps)) => steps.push(step),
+            None => patterns.last_mut().unwrap().steps.push(step),
+        }
+    }
+
+    check_pattern(patterns.last(), pattern_line, &open)?;
+    Ok(patterns)
+}
+
+// A bullet step: the shape's arguments, then options
+fn parse_bullets(directive: &str, words: &[&str], line: usize) -> Result<Step, PatternError> {
+    let split = words
+        .iter()
+        .position(|word| OPTIONS.contains(word))
+        .unwrap_or(words.len());
+    let (args, options) = words.split_at(split);
+
+    let shape = match (directive, args) {
+        ("shot", [angle]) => Shape::Shot(parse_float(angle, "angle", line)?),
+        ("fan", [count, arc]) => Shape::Fan {
+            count: parse_count(count, "bullet count", line)?,
+            arc: parse_float(arc, "arc", line)?,
+        },
+        ("ring", [count]) => Shape::Ring(parse_count(count, "bullet count", line)?),
+        ("aimed", []) => Shape::Aimed { count: 1, arc: 0.0 },
+        ("aimed", [count, arc]) => Shape::Aimed {
+            count: parse_count(count, "bullet count", line)?,
+            arc: parse_float(arc, "arc", line)?,
+        },
+        ("wall", [width]) | ("wall", [width, _]) => Shape::Wall {
+            width: parse_within(width, "wall width", 1..=WIDTH as i32, line)?,
+            gap: match args.get(1) {
+                Some(gap) => parse_within(gap, "gap", 0..=WIDTH as i32, line)?,
+                None => 0,
+            },
+        },
+        _ => {
+            let usage = match directive {
+                "shot" => "shot <angle>",
+                "fan" => "fan <count> <arc>",
+                "ring" => "ring <count>",
+                "aimed" => "aimed [<count> <arc>]",
+                _ => "wall <width> [<gap>]",
+            };
+            return Err(error(line, format!("expected '{}'", usage)));
+        }
+    };
+
+    let (mut speed, mut gravity) = (1.0, 0.0);
+    for option in options.chunks(2) {
+        match option {
+            ["speed", value] => speed = parse_float(value, "speed", line)?,
+            ["gravity", value] => gravity = parse_float(value, "gravity", line)?,
+            [name, _] => return Err(error(line, format!("unknown option '{}'", name))),
+            [name] => return Err(error(line, format!("'{}' needs a value", name))),
+            _ => unreachable!(),
+        }
+    }
+    if speed <= 0.0 {
+        return Err(error(line, "speed must be above 0"));
+    }
+    Ok(Step::Bullets {
+        shape,
+        speed,
+        gravity,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(
+    word: &str,
+    what: &str,
+    line: usize,
+) -> Result<T, PatternError> {
+    word.parse()
+        .map_err(|_| error(line, format!("invalid {} '{}'", what, word)))
+}
+
+// NaN and infinity parse as floats, but a bullet can't fly with them
+fn parse_float(word: &str, what: &str, line: usize) -> Result<f32, PatternError> {
+    let number: f32 = parse_number(word, what, line)?;
+    if !number.is_finite() {
+        return Err(error(line, format!("invalid {} '{}'", what, word)));
+    }
+    Ok(number)
+}
+
+// Sizes on the playfield, which is only WIDTH columns across
+fn parse_within(
+    word: &str,
+    what: &str,
+    range: RangeInclusive<i32>,
+    line: usize,
+) -> Result<i32, PatternError> {
+    let number = parse_number(word, what, line)?;
+    if !range.contains(&number) {
+        return Err(error(
+            line,
+            format!("{} must be from {} to {}", what, range.start(), range.end()),
+        ));
+    }
+    Ok(number)
+}
+
+// Counts must be at least 1
+fn parse_count(word: &str, what: &str, line: usize) -> Result<u32, PatternError> {
+    match parse_number(word, what, line)? {
+        0 => Err(error(line, format!("{} must be at least 1", what))),
+        count => Ok(count),
+    }
+}
+
+// Checks made once a pattern is complete
+fn check_pattern(
+    pattern: Option<&Pattern>,
+    pattern_line: usize,
+    open: &[(usize, u32, f32, Vec<Step>)],
+) -> Result<(), PatternError> {
+    if let Some(&(line, _, _, _)) = open.last() {
+        return Err(error(line, "'repeat' without an 'end'"));
+    }
+    match pattern {
+        Some(pattern) if pattern.steps.is_empty() => Err(error(
+            pattern_line,
+            format!("pattern '{}' has no steps", pattern.name),
+        )),
+        Some(pattern) if bullet_count(&pattern.steps) > MAX_BULLETS => Err(error(
+            pattern_line,
+            format!(
+                "pattern '{}' fires more than {} bullets",
+                pattern.name, MAX_BULLETS
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// How many bullets the steps fire, wherever they are fired from
+fn bullet_count(steps: &[Step]) -> u64 {
+    steps
+        .iter()
+        .map(|step| match step {
+            Step::Wait(_) => 0,
+            Step::Repeat { times, steps, .. } => {
+                (*times as u64).saturating_mul(bullet_count(steps))
+            }
+            Step::Bullets { shape, .. } => match *shape {
+                Shape::Shot(_) => 1,
+                Shape::Fan { count, .. } | Shape::Ring(count) | Shape::Aimed { count, .. } => {
+                    count as u64
+                }
+                Shape::Wall { width, gap } => (width as u64).div_ceil(gap as u64 + 1),
+            },
+        })
+        .fold(0, u64::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fired(source: &str) -> Vec<(u32, Bullet)> {
+        let patterns = Patterns {
+            patterns: parse_patterns(source).unwrap(),
+        };
+        patterns.fire("test", (30, 5), (30, 29))
+    }
+
+    #[test]
+    fn bundled_patterns_parse() {
+        let patterns = Patterns::bundled();
+        for name in ["down", "aimed", "spiral", "shell-wall"] {
+            assert!(patterns.get(name).is_some(), "{} is bundled", name);
+        }
+    }
+
+    #[test]
+    fn repeats_turn_and_wait() {
+        let bullets = fired("pattern test\nrepeat 3 90\n  shot 0\n  wait 2\nend\n");
+        let delays: Vec<u32> = bullets.iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(delays, [0, 2, 4]);
+        // Straight down, then to the right, then straight up
+        let (_, right) = &bullets[1];
+        assert!((right.vx - 1.0).abs() < 1e-4 && right.vy.abs() < 1e-4);
+        let (_, up) = &bullets[2];
+        assert!(up.vx.abs() < 1e-4 && (up.vy + 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn walls_and_options() {
+        let bullets = fired("pattern test\nwall 5 1 speed 0.5 gravity 0.1\n");
+        let columns: Vec<i32> = bullets.iter().map(|(_, b)| b.cell().0).collect();
+        assert_eq!(columns, [28, 30, 32]);
+        assert!(bullets.iter().all(|(_, b)| b.vy == 0.5 && b.ay == 0.1));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let cases = [
+            ("shot 0", "line 1: 'shot' must come after a 'pattern' line"),
+            (
+                "pattern a\nring 0",
+                "line 2: bullet count must be at least 1",
+            ),
+            ("pattern a\nfan 3", "line 2: expected 'fan <count> <arc>'"),
+            ("pattern a\nshot 0 speed", "line 2: 'speed' needs a value"),
+            (
+                "pattern a\nrepeat 2\nshot 0",
+                "line 2: 'repeat' without an 'end'",
+            ),
+            ("pattern a\nend", "line 2: 'end' without a 'repeat'"),
+            (
+                "pattern a\n\npattern b\nshot 0",
+                "line 1: pattern 'a' has no steps",
+            ),
+            (
+                "pattern a\nshot 0\npattern a",
+                "line 3: pattern 'a' is defined twice",
+            ),
+            (
+                "pattern a\nrepeat 51\nshot 0\nend",
+                "line 2: repeat count must be at most 50",
+            ),
+        ];
+        for (source, expected) in cases {
+            match parse_patterns(source) {
+                Ok(_) => panic!("{:?} should not parse", source),
+                Err(e) => assert_eq!(e.to_string(), expected, "for {:?}", source),
+            }
+        }
+    }
+
+    #[test]
+    fn patterns_are_capped_at_a_hundred_bullets() {
+        assert!(parse_patterns("pattern a\nrepeat 50\nfan 2 10\nend").is_ok());
+        let nested = "pattern a\nrepeat 50\nrepeat 50\nring 4000000000\nend\nend";
+        assert_eq!(
+            parse_patterns(nested).unwrap_err().to_string(),
+            "line 1: pattern 'a' fires more than 100 bullets"
+        );
+        let wide = "pattern a\nrepeat 3\nwall 60\nend";
+        assert_eq!(
+            parse_patterns(wide).unwrap_err().to_string(),
+            "line 1: pattern 'a' fires more than 100 bullets"
+        );
+    }
+
+    #[test]
+    fn walls_fit_the_playfield() {
+        assert!(parse_patterns("pattern a\nwall 60 60").is_ok());
+        let cases = [
+            ("wall 0", "line 2: wall width must be from 1 to 60"),
+            ("wall 61", "line 2: wall width must be from 1 to 60"),
+            ("wall 5 61", "line 2: gap must be from 0 to 60"),
+            ("wall 5 -1", "line 2: gap must be from 0 to 60"),
+            ("wall 5 4294967295", "line 2: invalid gap '4294967295'"),
+            ("wall 4294967297", "line 2: invalid wall width '4294967297'"),
+        ];
+        for (wall, expected) in cases {
+            let source = format!("pattern a\n{}", wall);
+            assert_eq!(parse_patterns(&source).unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn numbers_must_be_finite() {
+        let cases = [
+            ("shot NaN", "line 2: invalid angle 'NaN'"),
+            ("shot 0 speed NaN", "line 2: invalid speed 'NaN'"),
+            ("shot 0 speed inf", "line 2: invalid speed 'inf'"),
+            ("shot 0 gravity -inf", "line 2: invalid gravity '-inf'"),
+            ("fan 3 infinity", "line 2: invalid arc 'infinity'"),
+            ("aimed 3 nan", "line 2: invalid arc 'nan'"),
+            ("repeat 2 NaN\nshot 0\nend", "line 2: invalid turn 'NaN'"),
+        ];
+        for (step, expected) in cases {
+            let source = format!("pattern a\n{}", step);
+            assert_eq!(parse_patterns(&source).unwrap_err().to_string(), expected);
+        }
+    }
+}
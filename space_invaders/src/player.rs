@@ -0,0 +1,125 @@
+use std::time::Duration;
+use termion::color;
+use termion::event::Key;
+
+use crate::powerups::Powerup;
+use crate::{ticks, IMMUNITY_DURATION, TICK_DURATION, WIDTH};
+
+// Everything that belongs to one ship. Waves, bosses and bullets are shared, but in
+// co-op each player has their own lives, score, powerups and immunity
+
+// Something a player can do with their ship
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    Fire,
+}
+
+// Which player a key belongs to and what it does. Player one uses the arrow keys
+// and space, player two A, D and W on the same keyboard
+pub fn action_for(key: Key) -> Option<(usize, Action)> {
+    match key {
+        Key::Left => Some((0, Action::Left)),
+        Key::Right => Some((0, Action::Right)),
+        Key::Char(' ') => Some((0, Action::Fire)),
+        Key::Char('a') | Key::Char('A') => Some((1, Action::Left)),
+        Key::Char('d') | Key::Char('D') => Some((1, Action::Right)),
+        Key::Char('w') | Key::Char('W') => Some((1, Action::Fire)),
+        _ => None,
+    }
+}
+
+pub struct Player {
+    pub x: i32,
+    pub lives: usize,
+    pub score: u32,
+    pub powerup_active: Option<Powerup>,
+    pub powerup_timer: u8,
+    pub powerup_flash: Option<(Powerup, u8)>, // Instant powerup shown on the HUD, ticks left
+    pub last_hit_tick: Option<u64>,
+    pub shots: u32, // Shots fired this game, the UFO's score depends on it
+}
+
+impl Player {
+    pub fn new(x: i32) -> Self {
+        Player {
+            x,
+            lives: 3,
+            score: 0,
+            powerup_active: None,
+            powerup_timer: 0,
+            powerup_flash: None,
+            last_hit_tick: None,
+            shots: 0,
+        }
+    }
+
+    // Ships for `count` players, spread evenly along the bottom of the playfield
+    pub fn spawn(count: usize) -> Vec<Player> {
+        (0..count)
+            .map(|i| Player::new((WIDTH * (2 * i + 1) / (2 * count)) as i32))
+            .collect()
+    }
+
+    // Players out of lives sit out the rest of the game
+    pub fn in_game(&self) -> bool {
+        self.lives > 0
+    }
+
+    // Count down the active powerup and the HUD flash
+    pub fn tick_powerups(&mut self) {
+        if self.powerup_active.is_some() {
+            if self.powerup_timer > 0 {
+                self.powerup_timer -= 1;
+            } else {
+                self.powerup_active = None;
+            }
+        }
+        if let Some((powerup, ticks)) = self.powerup_flash {
+            self.powerup_flash = ticks.checked_sub(1).map(|ticks| (powerup, ticks));
+        }
+    }
+
+    // The player can't be hurt for a moment after being hit
+    pub fn is_immune(&self, tick: u64) -> bool {
+        self.last_hit_tick
+            .is_some_and(|hit| tick - hit < ticks(IMMUNITY_DURATION))
+    }
+
+    // Whether the Shield powerup is protecting the player
+    pub fn shield_active(&self) -> bool {
+        self.powerup_active == Some(Powerup::Shield)
+    }
+
+    // Points are doubled while Double Points is active
+    pub fn score_multiplier(&self) -> u32 {
+        if self.powerup_active == Some(Powerup::DoublePoints) {
+            2
+        } else {
+            1
+        }
+    }
+
+    // How many of this player's bullets may be on screen at once
+    pub fn max_bullets(&self) -> usize {
+        if self.powerup_active == Some(Powerup::RapidFire) {
+            6
+        } else {
+            3
+        }
+    }
+
+    // Time left on the active powerup
+    pub fn powerup_time_left(&self) -> Duration {
+        TICK_DURATION * self.powerup_timer as u32
+    }
+}
+
+// Each player's ship color, and the lighter one it flashes while immune
+pub fn colors(index: usize) -> (&'static dyn color::Color, &'static dyn color::Color) {
+    match index {
+        0 => (&color::Blue, &color::LightBlue),
+        _ => (&color::Magenta, &color::LightMagenta),
+    }
+}
@@ -0,0 +1,115 @@
+use rand::Rng;
+use termion::color;
+
+// Powerups dropped from the top of the screen. Timed ones stay active for a while,
+// instant ones take effect as soon as they are collected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Powerup {
+    BiggerLaser,
+    MultiLaser,
+    Shield,
+    RapidFire,
+    DoublePoints,
+    TimeSlow,
+    SmartBomb,
+    ExtraLife,
+}
+
+impl Powerup {
+    // Every powerup, in the order the tutorial lists them
+    pub const ALL: [Powerup; 8] = [
+        Powerup::BiggerLaser,
+        Powerup::MultiLaser,
+        Powerup::Shield,
+        Powerup::RapidFire,
+        Powerup::DoublePoints,
+        Powerup::TimeSlow,
+        Powerup::SmartBomb,
+        Powerup::ExtraLife,
+    ];
+
+    // Character drawn on the playfield and used in level files
+    pub fn glyph(self) -> char {
+        match self {
+            Powerup::BiggerLaser => 'B',
+            Powerup::MultiLaser => 'M',
+            Powerup::Shield => 'S',
+            Powerup::RapidFire => 'R',
+            Powerup::DoublePoints => '$',
+            Powerup::TimeSlow => '~',
+            Powerup::SmartBomb => '@',
+            Powerup::ExtraLife => '♥',
+        }
+    }
+
+    pub fn from_glyph(glyph: char) -> Option<Powerup> {
+        Powerup::ALL.iter().copied().find(|p| p.glyph() == glyph)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Powerup::BiggerLaser => "Bigger Laser",
+            Powerup::MultiLaser => "Multi Laser",
+            Powerup::Shield => "Shield",
+            Powerup::RapidFire => "Rapid Fire",
+            Powerup::DoublePoints => "Double Points",
+            Powerup::TimeSlow => "Time Slow",
+            Powerup::SmartBomb => "Smart Bomb",
+            Powerup::ExtraLife => "Extra Life",
+        }
+    }
+
+    // Shown on the tutorial screen
+    pub fn description(self) -> &'static str {
+        match self {
+            Powerup::BiggerLaser => "Bigger Laser (3-wide shot)",
+            Powerup::MultiLaser => "Multi-directional Laser (3-way shot)",
+            Powerup::Shield => "Shield (barrier that blocks bullets and enemies)",
+            Powerup::RapidFire => "Rapid Fire (more shots on screen at once)",
+            Powerup::DoublePoints => "Double Points (twice the score for kills)",
+            Powerup::TimeSlow => "Time Slow (enemies and their bullets slow down)",
+            Powerup::SmartBomb => "Smart Bomb (clears the screen)",
+            Powerup::ExtraLife => "Extra Life",
+        }
+    }
+
+    pub fn color(self) -> &'static dyn color::Color {
+        match self {
+            Powerup::BiggerLaser => &color::LightCyan,
+            Powerup::MultiLaser => &color::LightGreen,
+            Powerup::Shield => &color::Red,
+            Powerup::RapidFire => &color::Yellow,
+            Powerup::DoublePoints => &color::LightYellow,
+            Powerup::TimeSlow => &color::LightBlue,
+            Powerup::SmartBomb => &color::White,
+            Powerup::ExtraLife => &color::LightRed,
+        }
+    }
+
+    // Instant powerups take effect when collected instead of running on a timer
+    pub fn is_instant(self) -> bool {
+        matches!(self, Powerup::SmartBomb | Powerup::ExtraLife)
+    }
+
+    // How often this powerup drops compared to the others
+    fn drop_weight(self) -> u32 {
+        match self {
+            Powerup::SmartBomb => 2,
+            Powerup::ExtraLife => 1,
+            _ => 3,
+        }
+    }
+
+    // Pick a powerup to drop
+    pub fn random(rng: &mut impl Rng) -> Powerup {
+        let total: u32 = Powerup::ALL.iter().map(|p| p.drop_weight()).sum();
+        let mut roll = rng.gen_range(0..total);
+        for powerup in Powerup::ALL {
+            if roll < powerup.drop_weight() {
+                return powerup;
+            }
+            roll -= powerup.drop_weight();
+        }
+        Powerup::BiggerLaser
+    }
+}
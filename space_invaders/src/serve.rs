@@ -0,0 +1,485 @@
+use std::collections::VecDeque;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+use termion::color;
+use termion::cursor;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::screen::{ToAlternateScreen, ToMainScreen};
+
+use crate::display_game_over_screen::display_game_over_screen;
+use crate::display_start_screen::display_start_screen;
+use crate::display_tutorial_screen::display_tutorial_screen;
+use crate::game_mode::GameMode;
+use crate::leaderboard::Leaderboard;
+use crate::levels::Wave;
+use crate::patterns::Patterns;
+use crate::{
+    find_patterns, load_level_file, run_game, Game, GameExit, DEFAULT_LEVEL_FILE, HEIGHT, WIDTH,
+};
+
+// An arcade server: anyone can `telnet <host> 2323` and play a game of their own.
+// Every connection gets its own thread, game and randomness, so one player can't
+// affect another, and a session that panics only takes its own connection down.
+// The only thing sessions share is the leaderboard
+//
+//   space_invaders serve [--port <n>] [--levels <file>] [--patterns <file>] [--mode <arcade|classic>]
+//
+// Telnet clients start out in line mode with local echo. The server asks them to
+// send every key as it is typed and to report their window size (NAWS), then
+// strips telnet commands out of what they send so the rest reads as keys. What
+// the server sends needs no escaping: the game only draws text and UTF-8 never
+// contains the 0xFF byte that starts a telnet command
+
+const DEFAULT_PORT: u16 = 2323;
+// Players connected at once, past this new connections are turned away
+const MAX_SESSIONS: usize = 32;
+// How long a new session waits for the client's window size
+const NAWS_WAIT: Duration = Duration::from_millis(200);
+// Sessions end when the client types nothing for this long, or stops taking what
+// is sent for this long, so a stalled client can't keep its place forever
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Telnet command bytes and options
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const NAWS: u8 = 31;
+const LINEMODE: u8 = 34;
+
+// The server echoes (so the client doesn't), nobody waits for go-aheads, and the
+// client sends keys one at a time along with its window size
+#[rustfmt::skip]
+const NEGOTIATION: [u8; 15] = [
+    IAC, WILL, ECHO,
+    IAC, WILL, SUPPRESS_GO_AHEAD,
+    IAC, DO, SUPPRESS_GO_AHEAD,
+    IAC, DONT, LINEMODE,
+    IAC, DO, NAWS,
+];
+
+// What every session plays with
+struct Setup {
+    waves: Vec<Wave>,
+    patterns: Patterns,
+    mode: GameMode,
+    leaderboard: Mutex<Leaderboard>,
+}
+
+pub fn run(args: Vec<String>) -> io::Result<()> {
+    let mut port = DEFAULT_PORT;
+    let mut level_file = None;
+    let mut pattern_file = None;
+    let mut mode = GameMode::Arcade;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--port", Some(value)) => match value.parse() {
+                Ok(value) => port = value,
+                Err(_) => {
+                    eprintln!("--port needs a port number, got '{}'", value);
+                    std::process::exit(2);
+                }
+            },
+            ("--levels", Some(path)) => level_file = Some(path),
+            ("--patterns", Some(path)) => pattern_file = Some(path),
+            ("--mode", Some(name)) => match GameMode::from_name(&name) {
+                Some(chosen) => mode = chosen,
+                None => {
+                    eprintln!("--mode needs 'arcade' or 'classic'");
+                    std::process::exit(2);
+                }
+            },
+            ("--port" | "--levels" | "--patterns" | "--mode", None) => {
+                eprintln!("{} needs a value", arg);
+                std::process::exit(2);
+            }
+            _ => {
+                eprintln!("Unknown serve option: {}", arg);
+                eprintln!(
+                    "Usage: space_invaders serve [--port <n>] [--levels <file>] [--patterns <file>] [--mode <arcade|classic>]"
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Use the same waves the game would
+    let waves = match level_file {
+        Some(path) => load_level_file(&path),
+        None if Path::new(DEFAULT_LEVEL_FILE).exists() => load_level_file(DEFAULT_LEVEL_FILE),
+        None => Vec::new(),
+    };
+    let setup = Arc::new(Setup {
+        waves,
+        patterns: find_patterns(pattern_file.as_deref()),
+        mode,
+        leaderboard: Mutex::new(Leaderboard::load()),
+    });
+
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!(
+        "Serving Space Invaders on port {}, play with 'telnet <host> {}'",
+        port, port
+    );
+    let sessions = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Could not accept a connection: {}", e);
+                continue;
+            }
+        };
+        let address = stream
+            .peer_addr()
+            .map_or_else(|_| "unknown".to_string(), |address| address.to_string());
+        let slot = Slot::take(&sessions);
+        if slot.is_none() {
+            let _ = stream.write_all(b"The arcade is full, try again later\r\n");
+            continue;
+        }
+        println!(
+            "{} connected, {} playing",
+            address,
+            sessions.load(Ordering::SeqCst)
+        );
+        let setup = Arc::clone(&setup);
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = session(stream, &setup) {
+                println!("{} dropped: {}", address, e);
+            } else {
+                println!("{} left", address);
+            }
+        });
+    }
+    Ok(())
+}
+
+// One of the MAX_SESSIONS places, given back when the session ends however it ends
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(sessions: &Arc<AtomicUsize>) -> Option<Slot> {
+        sessions
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < MAX_SESSIONS).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| Slot(Arc::clone(sessions)))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Serve one connection until the player quits or goes away
+fn session(stream: TcpStream, setup: &Setup) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    // Clones share the socket, so these cover the reader and the writer too. A read
+    // timing out ends the key reader, and the session with it
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    // Frames are buffered so each goes out in one piece when it is flushed
+    let mut out = BufWriter::new(stream.try_clone()?);
+    out.write_all(&NEGOTIATION)?;
+    write!(out, "{}{}", ToAlternateScreen, cursor::Hide)?;
+
+    let window = Arc::new(Mutex::new(None));
+    let reader = TelnetReader::new(stream.try_clone()?, Arc::clone(&window));
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for key in reader.keys() {
+            match key {
+                Ok(key) if tx.send(key).is_ok() => {}
+                _ => return,
+            }
+        }
+    });
+
+    // Give the client a moment to report its window size before the first screen
+    out.flush()?;
+    thread::sleep(NAWS_WAIT);
+
+    let result = play(&mut out, &rx, setup, &window);
+    let _ = write!(out, "{}{}", cursor::Show, ToMainScreen);
+    let _ = out.flush();
+    let _ = stream.shutdown(Shutdown::Both);
+    result
+}
+
+// The start screen, games and game over screen, round and round
+fn play(
+    out: &mut impl Write,
+    rx: &mpsc::Receiver<Key>,
+    setup: &Setup,
+    window: &Mutex<Option<(u16, u16)>>,
+) -> io::Result<()> {
+    loop {
+        show_start_screen(out, window)?;
+        let player_count = loop {
+            match rx.recv() {
+                Ok(Key::Char('s') | Key::Char('S')) => break 1,
+                Ok(Key::Char('2')) => break 2,
+                Ok(Key::Char('t') | Key::Char('T')) => {
+                    display_tutorial_screen(out)?;
+                    while !matches!(rx.recv(), Ok(Key::Char('b') | Key::Char('B')) | Err(_)) {}
+                    show_start_screen(out, window)?;
+                }
+                Ok(Key::Char('q') | Key::Char('Q') | Key::Ctrl('c')) | Err(_) => return Ok(()),
+                Ok(_) => {}
+            }
+        };
+
+        let mut game = Game::new();
+        game.set_player_count(player_count);
+        game.waves = setup.waves.clone();
+        game.patterns = setup.patterns.clone();
+        game.mode = setup.mode;
+        game.high_score = lock(&setup.leaderboard).best(game.category());
+        game.start_level();
+        // There is nobody to hear sounds over telnet
+        if let GameExit::Quit = run_game(out, rx, None, &mut game, false, None, None)? {
+            return Ok(());
+        }
+
+        let (top, place) = {
+            let mut leaderboard = lock(&setup.leaderboard);
+            let place = leaderboard.record(game.category(), game.score());
+            let _ = leaderboard.save();
+            (leaderboard.top(game.category()), place)
+        };
+        let scores: Vec<u32> = game.players.iter().map(|player| player.score).collect();
+        display_game_over_screen(
+            out,
+            &scores,
+            game.level,
+            &top,
+            place,
+            game.start_time.elapsed(),
+            &game.stats,
+        )?;
+        loop {
+            match rx.recv() {
+                Ok(Key::Char('r') | Key::Char('R')) => break,
+                Ok(Key::Char('q') | Key::Char('Q') | Key::Ctrl('c')) | Err(_) => return Ok(()),
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+// The start screen, with a warning when the player's window is too small to fit
+// the game
+fn show_start_screen(out: &mut impl Write, window: &Mutex<Option<(u16, u16)>>) -> io::Result<()> {
+    display_start_screen(out)?;
+    let (width, height) = (WIDTH as u16, HEIGHT as u16 + 1);
+    if let Some((columns, rows)) = *lock(window) {
+        if columns < width || rows < height {
+            write!(
+                out,
+                "{}{}Your window is {}x{}, make it at least {}x{} to see the whole game{}",
+                cursor::Goto(1, 1),
+                color::Fg(color::LightRed),
+                columns,
+                rows,
+                width,
+                height,
+                color::Fg(color::Reset)
+            )?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+// A lock that is still good after a session panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Where TelnetReader is in the byte stream
+#[derive(Clone, Copy)]
+enum State {
+    Data,
+    Cr,             // After a carriage return, which telnet follows with NUL or LF
+    Iac,            // After IAC, a command follows
+    Option,         // After WILL, WONT, DO or DONT, an option follows
+    Subnegotiation, // Between IAC SB and IAC SE
+    SubnegotiationIac,
+}
+
+// Reads what a telnet client types, with the telnet commands taken out. Window
+// size reports are kept in `window`
+struct TelnetReader<R> {
+    stream: R,
+    window: Arc<Mutex<Option<(u16, u16)>>>,
+    state: State,
+    subnegotiation: Vec<u8>,
+    // Typed bytes not read yet. Whole reads from the stream are filtered in here,
+    // so the bytes of an escape sequence are handed out together and a lone ESC
+    // isn't mistaken for the Esc key
+    typed: VecDeque<u8>,
+}
+
+impl<R> TelnetReader<R> {
+    fn new(stream: R, window: Arc<Mutex<Option<(u16, u16)>>>) -> Self {
+        TelnetReader {
+            stream,
+            window,
+            state: State::Data,
+            subnegotiation: Vec::new(),
+            typed: VecDeque::new(),
+        }
+    }
+
+    // The byte to pass on, if it is part of what the player typed
+    fn filter(&mut self, byte: u8) -> Option<u8> {
+        let (state, kept) = match (self.state, byte) {
+            (State::Data | State::Cr, IAC) => (State::Iac, None),
+            (State::Data, b'\r') => (State::Cr, Some(byte)),
+            (State::Data, _) => (State::Data, Some(byte)),
+            (State::Cr, 0 | b'\n') => (State::Data, None),
+            (State::Cr, _) => (State::Data, Some(byte)),
+            (State::Iac, IAC) => (State::Data, Some(IAC)), // An escaped 0xFF
+            (State::Iac, WILL | WONT | DO | DONT) => (State::Option, None),
+            (State::Iac, SB) => {
+                self.subnegotiation.clear();
+                (State::Subnegotiation, None)
+            }
+            (State::Iac, _) | (State::Option, _) => (State::Data, None),
+            (State::Subnegotiation, IAC) => (State::SubnegotiationIac, None),
+            (State::Subnegotiation, _) => {
+                self.subnegotiation.push(byte);
+                (State::Subnegotiation, None)
+            }
+            (State::SubnegotiationIac, SE) => {
+                if let [NAWS, w1, w2, h1, h2] = self.subnegotiation[..] {
+                    let size = (u16::from_be_bytes([w1, w2]), u16::from_be_bytes([h1, h2]));
+                    *lock(&self.window) = Some(size);
+                }
+                (State::Data, None)
+            }
+            (State::SubnegotiationIac, _) => {
+                self.subnegotiation.push(byte);
+                (State::Subnegotiation, None)
+            }
+        };
+        self.state = state;
+        kept
+    }
+}
+
+impl<R: Read> Read for TelnetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reads that were all telnet commands carry on to the next
+        while self.typed.is_empty() {
+            let mut chunk = [0; 1024];
+            let read = self.stream.read(&mut chunk)?;
+            if read == 0 {
+                return Ok(0);
+            }
+            for &byte in &chunk[..read] {
+                if let Some(byte) = self.filter(byte) {
+                    self.typed.push_back(byte);
+                }
+            }
+        }
+        let count = buf.len().min(self.typed.len());
+        for (slot, byte) in buf.iter_mut().zip(self.typed.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A stream that hands out one chunk per read, like a client sending them apart
+    struct Chunks(VecDeque<Vec<u8>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some(chunk) = self.0.pop_front() else {
+                return Ok(0);
+            };
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    // What the player typed across the chunks, and the window size reported
+    fn typed(chunks: &[&[u8]]) -> (Vec<u8>, Option<(u16, u16)>) {
+        let window = Arc::new(Mutex::new(None));
+        let stream = Chunks(chunks.iter().map(|chunk| chunk.to_vec()).collect());
+        let mut reader = TelnetReader::new(stream, Arc::clone(&window));
+        let mut typed = Vec::new();
+        reader.read_to_end(&mut typed).unwrap();
+        let size = *lock(&window);
+        (typed, size)
+    }
+
+    #[test]
+    fn plain_keys_pass_through() {
+        assert_eq!(typed(&[b"s", b"\x1b[D"]), (b"s\x1b[D".to_vec(), None));
+    }
+
+    #[test]
+    fn escaped_iac_is_one_byte() {
+        assert_eq!(typed(&[&[b'a', IAC, IAC, b'b']]).0, [b'a', IAC, b'b']);
+        // Even when the escape is split between reads
+        assert_eq!(typed(&[&[b'a', IAC], &[IAC, b'b']]).0, [b'a', IAC, b'b']);
+    }
+
+    #[test]
+    fn option_negotiation_is_stripped() {
+        let chunk = [
+            IAC, WILL, NAWS, b'x', IAC, DO, ECHO, IAC, WONT, LINEMODE, IAC, DONT, 1, b'y',
+        ];
+        assert_eq!(typed(&[&chunk]).0, b"xy");
+        assert_eq!(typed(&[&[IAC, DO], &[SUPPRESS_GO_AHEAD, b'z']]).0, b"z");
+    }
+
+    #[test]
+    fn window_size_is_recorded() {
+        let naws = [IAC, SB, NAWS, 0, 80, 0, 24, IAC, SE, b'q'];
+        assert_eq!(typed(&[&naws]), (b"q".to_vec(), Some((80, 24))));
+    }
+
+    #[test]
+    fn window_size_can_arrive_in_pieces() {
+        let chunks: [&[u8]; 4] = [&[b'a', IAC], &[SB, NAWS, 1], &[44, 0, 50, IAC], &[SE, b'b']];
+        assert_eq!(typed(&chunks), (b"ab".to_vec(), Some((300, 50))));
+    }
+
+    #[test]
+    fn escaped_iac_inside_a_window_size_is_data() {
+        // A width of 255 columns is sent with its 0xFF byte doubled
+        let naws = [IAC, SB, NAWS, 0, IAC, IAC, 0, 40, IAC, SE];
+        assert_eq!(typed(&[&naws]).1, Some((255, 40)));
+    }
+
+    #[test]
+    fn line_endings_become_one_enter() {
+        assert_eq!(typed(&[b"\r\0", b"\r", b"\n", b"\rk"]).0, b"\r\r\rk");
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::agent;
+use crate::difficulty::Difficulty;
+use crate::events::{Cause, GameEvent};
+use crate::game_mode::GameMode;
+use crate::json::Json;
+use crate::levels::Wave;
+use crate::patterns::Patterns;
+use crate::{find_patterns, load_level_file, Game, DEFAULT_LEVEL_FILE, TICK_DURATION};
+
+// Plays many seeded games with an agent flying the ship, as fast as the machine
+// allows, and reports how they went, to see what a rules change does to the
+// balance before it ships. The report goes to stdout as CSV or JSON
+//
+//   space_invaders simulate [--games <n>] [--seed <n>] [--agent <autopilot|idle>]
+//       [--difficulty <easy|normal|hard>] [--mode <arcade|classic>] [--max-ticks <n>]
+//       [--format <csv|json>] [--levels <file>] [--patterns <file>]
+//
+// Games use seeds from --seed upwards, so the same options give the same report
+
+const DEFAULT_GAMES: u64 = 100;
+// Games still going after this long are cut off, an agent may never lose
+const DEFAULT_MAX_TICKS: u64 = 72_000; // An hour of play
+
+struct Settings {
+    agent: String,
+    difficulty: Difficulty,
+    mode: GameMode,
+    max_ticks: u64,
+    waves: Vec<Wave>,
+    patterns: Patterns,
+}
+
+// How one game went
+struct Outcome {
+    level: usize,
+    ticks: u64,
+    score: u32,
+    deaths: Vec<Cause>,
+    bosses_fought: u32,
+    bosses_killed: u32,
+    cut_off: bool,
+}
+
+pub fn run(args: Vec<String>) -> io::Result<()> {
+    let mut games = DEFAULT_GAMES;
+    let mut first_seed = 0;
+    let mut agent = "autopilot".to_string();
+    let mut difficulty = Difficulty::Normal;
+    let mut mode = GameMode::Arcade;
+    let mut max_ticks = DEFAULT_MAX_TICKS;
+    let mut json = false;
+    let mut level_file = None;
+    let mut pattern_file = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--games", Some(value)) => games = parse_number(&arg, &value),
+            ("--seed", Some(value)) => first_seed = parse_number(&arg, &value),
+            ("--max-ticks", Some(value)) => max_ticks = parse_number(&arg, &value),
+            ("--agent", Some(name)) if agent::by_name(&name).is_some() => agent = name,
+            ("--agent", Some(_)) => fail(&format!(
+                "--agent needs one of: {}",
+                agent::NAMES.join(", ")
+            )),
+            ("--difficulty", Some(name)) => match Difficulty::from_name(&name) {
+                Some(chosen) => difficulty = chosen,
+                None => fail("--difficulty needs 'easy', 'normal' or 'hard'"),
+            },
+            ("--mode", Some(name)) => match GameMode::from_name(&name) {
+                Some(chosen) => mode = chosen,
+                None => fail("--mode needs 'arcade' or 'classic'"),
+            },
+            ("--format", Some(format)) => match format.as_str() {
+                "csv" => json = false,
+                "json" => json = true,
+                _ => fail("--format needs 'csv' or 'json'"),
+            },
+            ("--levels", Some(path)) => level_file = Some(path),
+            ("--patterns", Some(path)) => pattern_file = Some(path),
+            (
+                "--games" | "--seed" | "--max-ticks" | "--agent" | "--difficulty" | "--mode"
+                | "--format" | "--levels" | "--patterns",
+                None,
+            ) => fail(&format!("{} needs a value", arg)),
+            _ => {
+                eprintln!("Unknown simulate option: {}", arg);
+                eprintln!(
+                    "Usage: space_invaders simulate [--games <n>] [--seed <n>] [--agent <autopilot|idle>]"
+                );
+                eprintln!(
+                    "           [--difficulty <easy|normal|hard>] [--mode <arcade|classic>] [--max-ticks <n>]"
+                );
+                eprintln!("           [--format <csv|json>] [--levels <file>] [--patterns <file>]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let settings = Settings {
+        agent,
+        difficulty,
+        mode,
+        max_ticks,
+        waves: match level_file {
+            Some(path) => load_level_file(&path),
+            None if Path::new(DEFAULT_LEVEL_FILE).exists() => load_level_file(DEFAULT_LEVEL_FILE),
+            None => Vec::new(),
+        },
+        patterns: find_patterns(pattern_file.as_deref()),
+    };
+
+    // Share the games out between a thread per core
+    let started = Instant::now();
+    let next = AtomicU64::new(0);
+    let outcomes = Mutex::new(Vec::new());
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let game = next.fetch_add(1, Ordering::Relaxed);
+                if game >= games {
+                    return;
+                }
+                let seed = first_seed.wrapping_add(game);
+                let outcome = play(seed, &settings);
+                outcomes
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push((seed, outcome));
+            });
+        }
+    });
+    let mut outcomes = outcomes.into_inner().unwrap_or_else(|e| e.into_inner());
+    // In the order the games were played, seeds wrap around past u64::MAX
+    outcomes.sort_by_key(|(seed, _)| seed.wrapping_sub(first_seed));
+    let outcomes: Vec<Outcome> = outcomes.into_iter().map(|(_, outcome)| outcome).collect();
+    eprintln!(
+        "Simulated {} games in {:.1}s",
+        outcomes.len(),
+        started.elapsed().as_secs_f32()
+    );
+
+    let report = report(&settings, first_seed, &outcomes);
+    if json {
+        println!("{}", report);
+    } else {
+        println!("statistic,value");
+        let mut rows = Vec::new();
+        flatten("", &report, &mut rows);
+        for (key, value) in rows {
+            println!("{},{}", csv_field(&key), csv_field(&value));
+        }
+    }
+    Ok(())
+}
+
+// Play one game to the end, or until it is cut off
+fn play(seed: u64, settings: &Settings) -> Outcome {
+    let mut game = Game::with_seed(seed);
+    game.mode = settings.mode;
+    game.difficulty = settings.difficulty;
+    game.waves = settings.waves.clone();
+    game.patterns = settings.patterns.clone();
+    game.start_level();
+    let mut agent = agent::by_name(&settings.agent).expect("agent names are checked up front");
+
+    let mut outcome = Outcome {
+        level: 1,
+        ticks: 0,
+        score: 0,
+        deaths: Vec::new(),
+        bosses_fought: 0,
+        bosses_killed: 0,
+        cut_off: false,
+    };
+    // A boss is only ever removed from the game by destroying it
+    let mut boss_up = false;
+    while !game.is_game_over() {
+        if game.tick >= settings.max_ticks {
+            outcome.cut_off = true;
+            break;
+        }
+        agent::steer(&mut game, 0, agent.as_mut());
+        game.update();
+        for event in game.events.drain(..) {
+            if let GameEvent::PlayerHit(cause) = event {
+                outcome.deaths.push(cause);
+            }
+            game.stats.record(&event);
+        }
+        match (boss_up, game.boss.is_some()) {
+            (false, true) => outcome.bosses_fought += 1,
+            (true, false) => outcome.bosses_killed += 1,
+            _ => {}
+        }
+        boss_up = game.boss.is_some();
+    }
+    outcome.level = game.level;
+    outcome.ticks = game.tick;
+    outcome.score = game.score();
+    outcome
+}
+
+// The statistics for a batch of games
+fn report(settings: &Settings, first_seed: u64, outcomes: &[Outcome]) -> Json {
+    let levels: Vec<f64> = outcomes.iter().map(|o| o.level as f64).collect();
+    let seconds: Vec<f64> = outcomes
+        .iter()
+        .map(|o| o.ticks as f64 * TICK_DURATION.as_secs_f64())
+        .collect();
+    let scores: Vec<f64> = outcomes.iter().map(|o| o.score as f64).collect();
+
+    // Games that ended on each level
+    let mut reached: BTreeMap<usize, u32> = BTreeMap::new();
+    for outcome in outcomes {
+        *reached.entry(outcome.level).or_default() += 1;
+    }
+    // Lives lost to each cause, most first
+    let mut causes: BTreeMap<String, u32> = BTreeMap::new();
+    for cause in outcomes.iter().flat_map(|o| &o.deaths) {
+        *causes.entry(cause.label()).or_default() += 1;
+    }
+    let lives_lost: u32 = causes.values().sum();
+    let mut causes: Vec<(String, u32)> = causes.into_iter().collect();
+    causes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let fought: u32 = outcomes.iter().map(|o| o.bosses_fought).sum();
+    let killed: u32 = outcomes.iter().map(|o| o.bosses_killed).sum();
+
+    Json::object([
+        (
+            "settings",
+            Json::object([
+                ("games", outcomes.len().into()),
+                ("first_seed", first_seed.into()),
+                ("agent", settings.agent.as_str().into()),
+                ("difficulty", settings.difficulty.name().into()),
+                ("mode", settings.mode.name().into()),
+                ("max_ticks", settings.max_ticks.into()),
+            ]),
+        ),
+        ("level", summary(&levels)),
+        (
+            "level_reached",
+            Json::Object(
+                reached
+                    .into_iter()
+                    .map(|(level, games)| (level.to_string(), games.into()))
+                    .collect(),
+            ),
+        ),
+        ("survival_seconds", summary(&seconds)),
+        ("score", summary(&scores)),
+        (
+            "deaths",
+            Json::object([
+                ("lives_lost", lives_lost.into()),
+                (
+                    "by_cause",
+                    Json::Object(
+                        causes
+                            .into_iter()
+                            .map(|(cause, count)| (cause, count.into()))
+                            .collect(),
+                    ),
+                ),
+            ]),
+        ),
+        (
+            "bosses",
+            Json::object([
+                ("fought", fought.into()),
+                ("killed", killed.into()),
+                (
+                    "kill_rate",
+                    (fought > 0).then(|| killed as f64 / fought as f64).into(),
+                ),
+            ]),
+        ),
+        (
+            "cut_off",
+            outcomes.iter().filter(|o| o.cut_off).count().into(),
+        ),
+    ])
+}
+
+// Mean, spread and percentiles of some numbers
+fn summary(values: &[f64]) -> Json {
+    if values.is_empty() {
+        return Json::Null;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    Json::object([
+        ("mean", round(mean).into()),
+        ("stddev", round(variance.sqrt()).into()),
+        ("min", percentile(0.0).into()),
+        ("p10", percentile(0.1).into()),
+        ("median", percentile(0.5).into()),
+        ("p90", percentile(0.9).into()),
+        ("max", percentile(1.0).into()),
+    ])
+}
+
+// Two decimal places are plenty for a report
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+// Turn nested objects into rows of dotted keys and values, for CSV
+fn flatten(prefix: &str, value: &Json, rows: &mut Vec<(String, String)>) {
+    match value {
+        Json::Object(fields) => {
+            for (key, value) in fields {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, rows);
+            }
+        }
+        Json::String(s) => rows.push((prefix.to_string(), s.clone())),
+        Json::Null => rows.push((prefix.to_string(), String::new())),
+        other => rows.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn parse_number(option: &str, value: &str) -> u64 {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("{} needs a number", option)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
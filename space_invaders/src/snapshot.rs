@@ -0,0 +1,459 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::boss::Boss;
+use crate::bosses;
+use crate::bullets::Bullet;
+use crate::bunkers::{BunkerLayout, Bunkers};
+use crate::display_pause_screen::display_pause_screen;
+use crate::enemies;
+use crate::player::Player;
+use crate::powerups::Powerup;
+use crate::world::PLAYER_ROW;
+use crate::{Enemy, Game};
+
+// Golden-frame checks for the renderer. A handful of scenarios, game states built
+// by hand, are drawn without a terminal and compared with frames stored in
+// snapshots/. Each scenario has two files: <name>.txt holds the glyphs as the
+// player would see them, and <name>.colors.txt the same grid with a letter for the
+// color of every cell, lower case for the eight ANSI colors and upper case for
+// their light versions (k r g y b m c w), blank for the default color and ? for
+// anything else
+//
+//   space_invaders snapshots [--bless] [--dir <dir>] [<scenario> ...]
+//
+// Without --bless the frames are checked and any that differ are shown; with it
+// the stored frames are replaced by the current ones, after a change to the look
+// of the game. Names pick scenarios to check, all of them by default. `cargo test`
+// checks every scenario too
+
+const DEFAULT_DIR: &str = "snapshots";
+
+// Sets up the game state to draw
+type Setup = fn() -> Game;
+
+// Every scenario, by name
+const SCENARIOS: [(&str, Setup); 7] = [
+    ("hud_solo", hud_solo),
+    ("hud_coop", hud_coop),
+    ("formation", formation),
+    ("boss_bar", boss_bar),
+    ("boss_intro", boss_intro),
+    ("explosions", explosions),
+    ("paused", paused),
+];
+
+pub fn run(args: Vec<String>) -> io::Result<()> {
+    let mut bless = false;
+    let mut dir = DEFAULT_DIR.to_string();
+    let mut names = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            "--dir" => match args.next() {
+                Some(path) => dir = path,
+                None => {
+                    eprintln!("--dir needs a directory");
+                    std::process::exit(2);
+                }
+            },
+            name if SCENARIOS.iter().any(|(n, _)| *n == name) => names.push(arg),
+            _ => {
+                eprintln!("Unknown snapshot option or scenario: {}", arg);
+                eprintln!(
+                    "Usage: space_invaders snapshots [--bless] [--dir <dir>] [<scenario> ...]"
+                );
+                let scenarios: Vec<&str> = SCENARIOS.iter().map(|(name, _)| *name).collect();
+                eprintln!("Scenarios: {}", scenarios.join(", "));
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let dir = Path::new(&dir);
+    if bless {
+        fs::create_dir_all(dir)?;
+    }
+    let mut failed = 0;
+    let chosen = SCENARIOS
+        .iter()
+        .filter(|(name, _)| names.is_empty() || names.iter().any(|n| n == name));
+    for &(name, scenario) in chosen {
+        for (file, actual) in golden_files(name, &Frame::capture(&scenario())) {
+            let path = dir.join(&file);
+            if bless {
+                fs::write(&path, &actual)?;
+                println!("Blessed {}", path.display());
+                continue;
+            }
+            match fs::read_to_string(&path) {
+                Ok(golden) if golden == actual => {}
+                Ok(golden) => {
+                    failed += 1;
+                    println!("FAIL {}", path.display());
+                    print_diff(&golden, &actual);
+                }
+                Err(e) => {
+                    failed += 1;
+                    println!("MISSING {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        println!(
+            "{} snapshot(s) differ, check the changes and rerun with --bless if they are right",
+            failed
+        );
+        std::process::exit(1);
+    }
+    if !bless {
+        println!("All snapshots match");
+    }
+    Ok(())
+}
+
+// The files a scenario's frame is stored in, and what they should hold
+fn golden_files(name: &str, frame: &Frame) -> [(String, String); 2] {
+    [
+        (format!("{}.txt", name), frame.glyphs()),
+        (format!("{}.colors.txt", name), frame.colors()),
+    ]
+}
+
+// The rows that changed, the stored one then the new one
+fn print_diff(golden: &str, actual: &str) {
+    let golden: Vec<&str> = golden.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    for row in 0..golden.len().max(actual.len()) {
+        let (before, after) = (golden.get(row), actual.get(row));
+        if before != after {
+            println!("  row {:2} - {}", row, before.unwrap_or(&""));
+            println!("  row {:2} + {}", row, after.unwrap_or(&""));
+        }
+    }
+}
+
+// The color of a cell as a terminal would show it
+#[derive(Clone, Copy, PartialEq)]
+enum Ink {
+    Default,
+    Palette(u8), // One of the 256 ANSI colors
+    Other,
+}
+
+impl Ink {
+    fn letter(self) -> char {
+        match self {
+            Ink::Default => ' ',
+            Ink::Palette(n) if n < 16 => "krgybmcwKRGYBMCW".as_bytes()[n as usize] as char,
+            _ => '?',
+        }
+    }
+
+    // The color after an SGR escape with these parameters. Bold and the other
+    // styles leave the color alone
+    fn after(self, params: &str) -> Ink {
+        let codes: Vec<&str> = params.split(';').collect();
+        match codes[..] {
+            ["38", "5", n] => n.parse().map_or(Ink::Other, Ink::Palette),
+            ["38", ..] => Ink::Other,
+            ["39"] | ["0"] | [""] => Ink::Default,
+            _ => self,
+        }
+    }
+}
+
+// A rendered frame read back into cells, the top row of the terminal first
+pub struct Frame {
+    rows: Vec<Vec<(char, Ink)>>,
+}
+
+impl Frame {
+    // Draw the game as run_game sends it to the terminal, with the pause screen over
+    // a paused game, and read the escapes back out
+    pub fn capture(game: &Game) -> Frame {
+        let mut output = game.render().into_bytes();
+        if game.paused {
+            display_pause_screen(&mut output).expect("writing to a Vec can't fail");
+        }
+        Frame::read(&String::from_utf8_lossy(&output))
+    }
+
+    // Play terminal output onto a grid of cells, starting in the top-left corner.
+    // Only CSI escapes are used, ESC [ then parameters up to a letter; colors and
+    // cursor moves are followed and the rest are skipped
+    fn read(output: &str) -> Frame {
+        let mut rows: Vec<Vec<(char, Ink)>> = Vec::new();
+        let (mut row, mut column) = (0, 0);
+        let mut ink = Ink::Default;
+        let mut chars = output.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => column = 0,
+                '\n' => row += 1,
+                '\x1b' => {
+                    if chars.next() != Some('[') {
+                        continue;
+                    }
+                    let mut params = String::new();
+                    for c in chars.by_ref() {
+                        match c {
+                            'm' => ink = ink.after(&params),
+                            // Goto, to a row and column counted from 1
+                            'H' => {
+                                let mut at = params.split(';').map(|n| n.parse().unwrap_or(1));
+                                row = at.next().unwrap_or(1usize).saturating_sub(1);
+                                column = at.next().unwrap_or(1usize).saturating_sub(1);
+                            }
+                            c if !c.is_ascii_alphabetic() => {
+                                params.push(c);
+                                continue;
+                            }
+                            _ => {}
+                        }
+                        break;
+                    }
+                }
+                c => {
+                    if rows.len() <= row {
+                        rows.resize(row + 1, Vec::new());
+                    }
+                    let line = &mut rows[row];
+                    if line.len() <= column {
+                        line.resize(column + 1, (' ', Ink::Default));
+                    }
+                    line[column] = (c, ink);
+                    column += 1;
+                }
+            }
+        }
+        Frame { rows }
+    }
+
+    // One line of glyphs per row
+    pub fn glyphs(&self) -> String {
+        self.lines(|(c, _)| c)
+    }
+
+    // One line of color letters per row, lined up with the glyphs
+    pub fn colors(&self) -> String {
+        self.lines(|(c, ink)| if c == ' ' { ' ' } else { ink.letter() })
+    }
+
+    // Trailing blanks are dropped so editors don't fight over them
+    fn lines(&self, cell: impl Fn((char, Ink)) -> char) -> String {
+        let mut text = String::new();
+        for row in &self.rows {
+            let line: String = row.iter().map(|&c| cell(c)).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// Builds a game state by hand for a snapshot: an empty playfield with one ship in
+// the middle, no bunkers, no UFO and a high score of 0, with everything else placed
+// where it is asked for. Positions are in world coordinates
+pub struct Scenario {
+    game: Game,
+}
+
+impl Scenario {
+    pub fn empty() -> Self {
+        let mut game = Game::with_seed(0);
+        game.high_score = 0;
+        game.ufo = None;
+        Scenario { game }
+    }
+
+    pub fn players(mut self, count: usize) -> Self {
+        self.game.players = Player::spawn(count);
+        self
+    }
+
+    // Change one of the players
+    pub fn player(mut self, index: usize, change: impl FnOnce(&mut Player)) -> Self {
+        change(&mut self.game.players[index]);
+        self
+    }
+
+    pub fn level(mut self, level: usize) -> Self {
+        self.game.level = level;
+        self
+    }
+
+    pub fn high_score(mut self, score: u32) -> Self {
+        self.game.high_score = score;
+        self
+    }
+
+    pub fn bunkers(mut self) -> Self {
+        self.game.bunkers = Bunkers::build(&BunkerLayout::default());
+        self
+    }
+
+    // An enemy of the kind drawn with `glyph`
+    pub fn enemy(mut self, glyph: char, x: i32, y: i32) -> Self {
+        let kind = enemies::kind_for(glyph).expect("scenarios use known enemy glyphs");
+        let enemy = Enemy::new(x, y, kind, &mut self.game.rng);
+        self.game.enemies.push(enemy);
+        self
+    }
+
+    pub fn bullet(mut self, bullet: Bullet) -> Self {
+        self.game.bullets.push(bullet);
+        self
+    }
+
+    pub fn powerup(mut self, powerup: Powerup, x: i32, y: i32) -> Self {
+        self.game.powerups.push((x, y, powerup));
+        self
+    }
+
+    // The boss for `level` with its top-left corner at (x, y), done with its
+    // entrance unless `intro` is set, and worn down by `damage` on every part but
+    // the cores
+    pub fn boss(mut self, level: usize, x: i32, y: i32, intro: bool, damage: u16) -> Self {
+        let mut boss = Boss::new(bosses::for_level(level), level);
+        boss.x = x;
+        boss.y = y;
+        if !intro {
+            boss.intro_ticks = 0;
+        }
+        boss.bomb(damage);
+        self.game.boss = Some(boss);
+        self
+    }
+
+    // An explosion `frame` ticks into its animation
+    pub fn explosion(mut self, x: i32, y: i32, frame: u8) -> Self {
+        self.game.explosions.push((x, y, frame));
+        self
+    }
+
+    // Points floating up from (x, y), `age` ticks old
+    pub fn popup(mut self, x: i32, y: i32, points: u32, age: u8) -> Self {
+        self.game.popups.push((x, y, points, age));
+        self
+    }
+
+    pub fn paused(mut self) -> Self {
+        self.game.paused = true;
+        self
+    }
+
+    // The clock starts now, so the HUD shows 00:00
+    pub fn build(mut self) -> Game {
+        self.game.start_time = Instant::now();
+        self.game
+    }
+}
+
+fn hud_solo() -> Game {
+    Scenario::empty()
+        .level(3)
+        .high_score(5000)
+        .player(0, |player| {
+            player.score = 1230;
+            player.lives = 2;
+            player.powerup_active = Some(Powerup::RapidFire);
+            player.powerup_timer = 90;
+        })
+        .build()
+}
+
+fn hud_coop() -> Game {
+    Scenario::empty()
+        .players(2)
+        .level(7)
+        .high_score(12000)
+        .player(0, |player| {
+            player.score = 4100;
+            player.powerup_active = Some(Powerup::Shield);
+            player.powerup_timer = 40;
+        })
+        .player(1, |player| {
+            player.score = 3550;
+            player.lives = 1;
+            player.powerup_flash = Some((Powerup::SmartBomb, 10));
+        })
+        .build()
+}
+
+fn formation() -> Game {
+    let mut scenario = Scenario::empty().bunkers();
+    for (i, kind) in enemies::ALL_KINDS.iter().enumerate() {
+        let x = 6 + 6 * i as i32;
+        scenario = scenario.enemy(kind.glyph(), x, 4).enemy(kind.glyph(), x, 6);
+    }
+    scenario
+        .bullet(Bullet::player(30, PLAYER_ROW - 6))
+        .bullet(Bullet::new(12, 10, 0.0, 1.0, true))
+        .bullet(Bullet::new(40, 14, 0.5, 1.0, true))
+        .powerup(Powerup::MultiLaser, 20, 12)
+        .powerup(Powerup::ExtraLife, 44, 16)
+        .build()
+}
+
+fn boss_bar() -> Game {
+    Scenario::empty()
+        .level(5)
+        .boss(5, 24, 3, false, 3)
+        .bullet(Bullet::player(28, PLAYER_ROW - 4))
+        .build()
+}
+
+fn boss_intro() -> Game {
+    Scenario::empty().level(10).boss(10, 22, 3, true, 0).build()
+}
+
+fn explosions() -> Game {
+    Scenario::empty()
+        .enemy('N', 20, 8)
+        .explosion(24, 8, 0)
+        .explosion(28, 8, 1)
+        .explosion(32, 8, 2)
+        .popup(24, 10, 10, 0)
+        .popup(36, 12, 250, 20)
+        .build()
+}
+
+// Nothing of the game shows through the pause screen
+fn paused() -> Game {
+    Scenario::empty()
+        .bunkers()
+        .enemy('N', 20, 5)
+        .bullet(Bullet::player(30, 20))
+        .paused()
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_match_the_golden_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_DIR);
+        let mut differ = Vec::new();
+        for &(name, scenario) in &SCENARIOS {
+            for (file, actual) in golden_files(name, &Frame::capture(&scenario())) {
+                if fs::read_to_string(dir.join(&file)).ok() != Some(actual) {
+                    differ.push(file);
+                }
+            }
+        }
+        assert!(
+            differ.is_empty(),
+            "frames differ from {}: {:?}, run `space_invaders snapshots` to see how",
+            dir.display(),
+            differ
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpListener};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use termion::{clear, color, cursor};
+
+use crate::events::GameEvent;
+
+// Lets other terminals watch a game as it is played. Viewers connect to a TCP port
+// or a Unix socket, with something like 'nc localhost 4000' or 'nc -U <path>', and
+// are sent every frame the player sees along with a line of recent events. They
+// can't send anything back.
+//
+// A viewer is sent a keyframe that redraws the whole screen as soon as they connect,
+// and after that only the rows that change. Each viewer is written to from its own
+// thread through a short queue, so a slow viewer never holds up the game. One that
+// falls behind skips frames and gets a keyframe once it catches up
+
+// Frames queued for a viewer before it starts missing them
+const QUEUE: usize = 4;
+// Recent events shown under the playfield
+const TICKER: usize = 4;
+
+pub enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Endpoint {
+    // 'unix:<path>' for a Unix socket, otherwise a TCP address or just a port
+    pub fn parse(text: &str) -> Self {
+        match text.strip_prefix("unix:") {
+            Some(path) => Endpoint::Unix(PathBuf::from(path)),
+            None if text.parse::<u16>().is_ok() => Endpoint::Tcp(format!("127.0.0.1:{}", text)),
+            None => Endpoint::Tcp(text.to_string()),
+        }
+    }
+
+    // How a teammate can start watching
+    pub fn watch_command(&self) -> String {
+        match self {
+            Endpoint::Tcp(address) => match address.rsplit_once(':') {
+                Some((host, port)) => format!("nc {} {}", host, port),
+                None => format!("nc {}", address),
+            },
+            Endpoint::Unix(path) => format!("nc -U {}", path.display()),
+        }
+    }
+}
+
+struct Viewer {
+    frames: SyncSender<Arc<[u8]>>,
+    needs_keyframe: bool,
+}
+
+// What the game and the thread taking viewers share
+#[derive(Default)]
+struct Shared {
+    viewers: Vec<Viewer>,
+    keyframe: Option<Arc<[u8]>>, // The latest frame in full, for viewers who join late
+}
+
+pub struct Spectators {
+    shared: Arc<Mutex<Shared>>,
+    rows: Vec<String>, // The last frame sent, by screen row
+    ticker: VecDeque<String>,
+    socket: Option<PathBuf>, // Removed again when the game closes
+}
+
+impl Spectators {
+    // Start taking viewers in the background
+    pub fn open(endpoint: &Endpoint) -> io::Result<Self> {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let socket = match endpoint {
+            Endpoint::Tcp(address) => {
+                let listener = TcpListener::bind(address)?;
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let _ = stream.set_nodelay(true);
+                        let _ = stream.shutdown(Shutdown::Read);
+                        add_viewer(&shared, stream);
+                    }
+                });
+                None
+            }
+            Endpoint::Unix(path) => {
+                // A socket left behind by an earlier game is in the way
+                if UnixStream::connect(path).is_err() {
+                    let _ = fs::remove_file(path);
+                }
+                let listener = UnixListener::bind(path)?;
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let _ = stream.shutdown(Shutdown::Read);
+                        add_viewer(&shared, stream);
+                    }
+                });
+                Some(path.clone())
+            }
+        };
+        Ok(Spectators {
+            shared,
+            rows: Vec::new(),
+            ticker: VecDeque::new(),
+            socket,
+        })
+    }
+
+    // Note events for the ticker. Called before the game loop drains them
+    pub fn record(&mut self, events: &[GameEvent]) {
+        for event in events {
+            if let Some(text) = describe(event) {
+                self.ticker.push_front(text);
+                self.ticker.truncate(TICKER);
+            }
+        }
+    }
+
+    // Send a rendered frame to every viewer
+    pub fn show(&mut self, frame: &str, paused: bool) {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut rows: Vec<String> = frame.split("\r\n").map(String::from).collect();
+        rows.push(String::new());
+        rows.push(self.ticker_line(shared.viewers.len(), paused));
+
+        let mut keyframe = format!("{}{}", clear::All, cursor::Hide);
+        let mut delta = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            let goto = cursor::Goto(1, i as u16 + 1);
+            keyframe.push_str(&format!("{}{}", goto, row));
+            if self.rows.get(i) != Some(row) {
+                delta.push_str(&format!("{}{}{}", goto, clear::CurrentLine, row));
+            }
+        }
+        // Rows the last frame had and this one doesn't
+        for i in rows.len()..self.rows.len() {
+            delta.push_str(&format!(
+                "{}{}",
+                cursor::Goto(1, i as u16 + 1),
+                clear::CurrentLine
+            ));
+        }
+        let keyframe: Arc<[u8]> = keyframe.into_bytes().into();
+        let delta: Arc<[u8]> = delta.into_bytes().into();
+
+        shared.viewers.retain_mut(|viewer| {
+            let bytes = if viewer.needs_keyframe {
+                &keyframe
+            } else {
+                &delta
+            };
+            match viewer.frames.try_send(Arc::clone(bytes)) {
+                Ok(()) => {
+                    viewer.needs_keyframe = false;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    viewer.needs_keyframe = true;
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+        shared.keyframe = Some(keyframe);
+        self.rows = rows;
+    }
+
+    fn ticker_line(&self, watching: usize, paused: bool) -> String {
+        let mut line = format!(
+            "{}LIVE{} {} watching",
+            color::Fg(color::LightRed),
+            color::Fg(color::Reset),
+            watching
+        );
+        if paused {
+            line.push_str(&format!(
+                " | {}PAUSED{}",
+                color::Fg(color::Yellow),
+                color::Fg(color::Reset)
+            ));
+        }
+        if !self.ticker.is_empty() {
+            let recent: Vec<&str> = self.ticker.iter().map(String::as_str).collect();
+            line.push_str(" | ");
+            line.push_str(&recent.join(", "));
+        }
+        line
+    }
+}
+
+impl Drop for Spectators {
+    fn drop(&mut self) {
+        if let Some(path) = &self.socket {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Give a new viewer its own writer thread, starting with the latest keyframe
+fn add_viewer(shared: &Mutex<Shared>, mut stream: impl Write + Send + 'static) {
+    let (frames, queued) = mpsc::sync_channel::<Arc<[u8]>>(QUEUE);
+    thread::spawn(move || {
+        for frame in queued {
+            if stream
+                .write_all(&frame)
+                .and_then(|_| stream.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+    });
+    let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+    let needs_keyframe = match &shared.keyframe {
+        Some(keyframe) => frames.try_send(Arc::clone(keyframe)).is_err(),
+        None => true,
+    };
+    shared.viewers.push(Viewer {
+        frames,
+        needs_keyframe,
+    });
+}
+
+// What the ticker says about an event. Shots and glancing hits happen too often to list
+fn describe(event: &GameEvent) -> Option<String> {
+    match *event {
+        GameEvent::Shot { .. } | GameEvent::EnemyHit { destroyed: false } => None,
+        GameEvent::EnemyHit { destroyed: true } => Some("enemy destroyed".to_string()),
+        GameEvent::BossHit { destroyed: false } => Some("boss hit".to_string()),
+        GameEvent::BossHit { destroyed: true } => Some("boss destroyed!".to_string()),
+        GameEvent::ShipCollision => Some("ship rammed".to_string()),
+        GameEvent::ShieldBlock => Some("shield blocked a hit".to_string()),
+        GameEvent::PlayerHit(cause) => Some(format!("player {}!", cause.label())),
+        GameEvent::PowerupCollected(powerup) => Some(format!("{} collected", powerup.name())),
+        GameEvent::UfoAppeared => Some("UFO sighted".to_string()),
+        GameEvent::UfoHit { points } => Some(format!("UFO shot down for {}", points)),
+    }
+}
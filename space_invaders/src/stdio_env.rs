@@ -0,0 +1,319 @@
+use std::io::{self, BufRead, BufWriter, Write};
+
+use crate::game_mode::GameMode;
+use crate::json::Json;
+use crate::levels::Wave;
+use crate::patterns::Patterns;
+use crate::player::Action;
+use crate::Game;
+
+// A game driven over stdin and stdout, one JSON object per line, for agents that
+// live in another process such as a reinforcement learning trainer. There is no
+// terminal UI and no clock: a tick is played as soon as it is asked for
+//
+//   space_invaders --stdio-env [--levels <file>] [--patterns <file>] [--mode <arcade|classic>]
+//
+// Requests:
+//   {"cmd": "reset", "seed": 7, "mode": "classic", "observation": "grid", "life_penalty": 100}
+//   {"cmd": "step", "action": "left_fire", "repeat": 4}
+//   {"cmd": "close"}
+//
+// Everything but "cmd" is optional. A reset starts a new game, with a random seed
+// unless one is given, and observations as an entity list unless "grid" is asked
+// for. Seeds are any u64, written as plain digits to get past 2^53 exactly. A step plays `repeat` ticks, 1 by default, with the action pressed on each.
+// An action is a name from ACTIONS or its index there, or a list of "left",
+// "right" and "fire".
+//
+// Resets and steps are answered with
+//   {"observation": ..., "reward": 10, "score_delta": 10, "lives_lost": 0,
+//    "done": false, "info": {"tick": 1, "level": 1, "score": 10, "lives": 3}}
+// where the reward is the points scored less `life_penalty` for every life lost.
+// A request that can't be carried out is answered with {"error": "..."}
+
+// The discrete action space, in index order
+const ACTIONS: [(&str, &[Action]); 6] = [
+    ("none", &[]),
+    ("left", &[Action::Left]),
+    ("right", &[Action::Right]),
+    ("fire", &[Action::Fire]),
+    ("left_fire", &[Action::Left, Action::Fire]),
+    ("right_fire", &[Action::Right, Action::Fire]),
+];
+const DEFAULT_LIFE_PENALTY: f64 = 100.0;
+// Longest step, so one request can't hang the environment
+const MAX_REPEAT: u64 = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Observation {
+    Entities,
+    Grid,
+}
+
+// The game being played, and how to report on it
+struct Env {
+    game: Game,
+    observation: Observation,
+    life_penalty: f64,
+}
+
+pub fn run(waves: Vec<Wave>, patterns: Patterns, mode: GameMode) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut env: Option<Env> = None;
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match Json::parse(&line) {
+            Ok(request) => match request.get("cmd").and_then(Json::as_str) {
+                Some("reset") => reset(&request, &waves, &patterns, mode).map(|new| {
+                    let reply = new.reply(0, 0);
+                    env = Some(new);
+                    reply
+                }),
+                Some("step") => match &mut env {
+                    Some(env) => env.step(&request),
+                    None => Err("reset before the first step".to_string()),
+                },
+                Some("close") => break,
+                Some(other) => Err(format!("unknown cmd '{}'", other)),
+                None => Err("a request needs a \"cmd\"".to_string()),
+            },
+            Err(e) => Err(format!("bad JSON: {}", e)),
+        };
+        let reply = reply.unwrap_or_else(|e| Json::object([("error", e.into())]));
+        writeln!(out, "{}", reply)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn reset(
+    request: &Json,
+    waves: &[Wave],
+    patterns: &Patterns,
+    mode: GameMode,
+) -> Result<Env, String> {
+    let seed = match request.get("seed") {
+        None | Some(Json::Null) => rand::random(),
+        Some(seed) => seed.as_u64().ok_or("\"seed\" must be a whole number")?,
+    };
+    let mode = match request.get("mode").map(Json::as_str) {
+        None => mode,
+        Some(name) => name
+            .and_then(GameMode::from_name)
+            .ok_or("\"mode\" must be \"arcade\" or \"classic\"")?,
+    };
+    let observation = match request.get("observation").map(Json::as_str) {
+        None | Some(Some("entities")) => Observation::Entities,
+        Some(Some("grid")) => Observation::Grid,
+        Some(_) => return Err("\"observation\" must be \"entities\" or \"grid\"".to_string()),
+    };
+    let life_penalty = match request.get("life_penalty") {
+        None => DEFAULT_LIFE_PENALTY,
+        Some(penalty) => penalty
+            .as_f64()
+            .ok_or("\"life_penalty\" must be a number")?,
+    };
+
+    let mut game = Game::with_seed(seed);
+    game.mode = mode;
+    game.waves = waves.to_vec();
+    game.patterns = patterns.clone();
+    game.start_level();
+    Ok(Env {
+        game,
+        observation,
+        life_penalty,
+    })
+}
+
+impl Env {
+    fn step(&mut self, request: &Json) -> Result<Json, String> {
+        if self.game.is_game_over() {
+            return Err("the game is over, reset to play again".to_string());
+        }
+        let actions = parse_action(request.get("action").unwrap_or(&Json::Null))?;
+        let repeat = match request.get("repeat") {
+            None => 1,
+            Some(repeat) => repeat
+                .as_u64()
+                .filter(|repeat| (1..=MAX_REPEAT).contains(repeat))
+                .ok_or(format!("\"repeat\" must be from 1 to {}", MAX_REPEAT))?,
+        };
+
+        let score = self.game.score();
+        let lives = self.game.players[0].lives;
+        for _ in 0..repeat {
+            for &action in &actions {
+                self.game.handle_action(0, action);
+            }
+            self.game.update();
+            for event in self.game.events.drain(..) {
+                self.game.stats.record(&event);
+            }
+            if self.game.is_game_over() {
+                break;
+            }
+        }
+        // Bonus lives from health enemies don't make up for lives lost
+        let lives_lost = lives.saturating_sub(self.game.players[0].lives);
+        Ok(self.reply(self.game.score() - score, lives_lost))
+    }
+
+    fn reply(&self, score_delta: u32, lives_lost: usize) -> Json {
+        let game = &self.game;
+        let reward = score_delta as f64 - self.life_penalty * lives_lost as f64;
+        let observation = match self.observation {
+            Observation::Entities => entities(game),
+            Observation::Grid => grid(game),
+        };
+        Json::object([
+            ("observation", observation),
+            ("reward", reward.into()),
+            ("score_delta", score_delta.into()),
+            ("lives_lost", lives_lost.into()),
+            ("done", game.is_game_over().into()),
+            (
+                "info",
+                Json::object([
+                    ("tick", game.tick.into()),
+                    ("level", game.level.into()),
+                    ("score", game.score().into()),
+                    ("lives", game.players[0].lives.into()),
+                ]),
+            ),
+        ])
+    }
+}
+
+// A name or index from ACTIONS, or a list of single moves
+fn parse_action(action: &Json) -> Result<Vec<Action>, String> {
+    let named = |name: &str| {
+        ACTIONS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, a)| a.to_vec())
+    };
+    match action {
+        Json::Null => Ok(Vec::new()),
+        Json::String(name) => named(name).ok_or(format!("unknown action '{}'", name)),
+        Json::Number(_) | Json::Integer(_) => action
+            .as_u64()
+            .and_then(|i| ACTIONS.get(i as usize))
+            .map(|(_, actions)| actions.to_vec())
+            .ok_or(format!("action numbers go from 0 to {}", ACTIONS.len() - 1)),
+        Json::Array(moves) => moves
+            .iter()
+            .map(|m| match m.as_str() {
+                Some("left") => Ok(Action::Left),
+                Some("right") => Ok(Action::Right),
+                Some("fire") => Ok(Action::Fire),
+                _ => Err("a list of actions holds \"left\", \"right\" and \"fire\"".to_string()),
+            })
+            .collect(),
+        _ => Err("\"action\" must be a name, a number or a list".to_string()),
+    }
+}
+
+// Everything on the playfield, in world coordinates
+fn entities(game: &Game) -> Json {
+    let player = &game.players[0];
+    let player = Json::object([
+        ("x", player.x.into()),
+        ("lives", player.lives.into()),
+        ("immune", player.is_immune(game.tick).into()),
+        ("powerup", player.powerup_active.map(|p| p.name()).into()),
+        ("powerup_ticks", player.powerup_timer.into()),
+    ]);
+    let enemies = game
+        .enemies
+        .iter()
+        .map(|enemy| {
+            Json::object([
+                ("x", enemy.x.into()),
+                ("y", enemy.y.into()),
+                ("kind", enemy.kind.glyph().to_string().into()),
+                ("health", enemy.health.into()),
+            ])
+        })
+        .collect();
+    let bullets = game
+        .bullets
+        .iter()
+        .map(|bullet| {
+            Json::object([
+                ("x", bullet.x.into()),
+                ("y", bullet.y.into()),
+                ("vx", bullet.vx.into()),
+                ("vy", bullet.vy.into()),
+                ("enemy", bullet.is_enemy.into()),
+            ])
+        })
+        .collect();
+    let powerups = game
+        .powerups
+        .iter()
+        .map(|&(x, y, powerup)| {
+            Json::object([
+                ("x", x.into()),
+                ("y", y.into()),
+                ("kind", powerup.name().into()),
+            ])
+        })
+        .collect();
+    let boss = game.boss.as_ref().map(|boss| {
+        let parts = boss
+            .parts
+            .iter()
+            .map(|part| {
+                let (x, y) = boss.part_center(part);
+                Json::object([
+                    ("x", x.into()),
+                    ("y", y.into()),
+                    ("kind", format!("{:?}", part.kind).to_lowercase().into()),
+                    ("health", part.health.into()),
+                ])
+            })
+            .collect();
+        Json::object([
+            ("name", boss.kind.name().into()),
+            ("x", boss.x.into()),
+            ("y", boss.y.into()),
+            ("width", boss.width().into()),
+            ("height", boss.height().into()),
+            ("phase", boss.phase().into()),
+            ("health", boss.health_fraction().into()),
+            ("parts", Json::Array(parts)),
+        ])
+    });
+    let ufo = game.ufo.as_ref().map(|ufo| {
+        let (x, y) = ufo.center();
+        Json::object([("x", x.into()), ("y", y.into())])
+    });
+    let bunkers = game
+        .bunkers
+        .cells()
+        .map(|(x, y, _)| Json::from(vec![x, y]))
+        .collect();
+    Json::object([
+        ("player", player),
+        ("enemies", Json::Array(enemies)),
+        ("bullets", Json::Array(bullets)),
+        ("powerups", Json::Array(powerups)),
+        ("boss", boss.into()),
+        ("ufo", ufo.into()),
+        ("bunkers", Json::Array(bunkers)),
+    ])
+}
+
+// The playfield as drawn, one string of glyphs per row
+fn grid(game: &Game) -> Json {
+    game.playfield()
+        .iter()
+        .map(|row| row.iter().map(|&(glyph, _)| glyph).collect::<String>())
+        .collect::<Vec<String>>()
+        .into()
+}
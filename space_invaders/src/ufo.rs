@@ -0,0 +1,94 @@
+use crate::collision::Cell;
+use crate::world;
+use crate::WIDTH;
+
+// The mystery ship crosses the top of the playfield every so often. Like the
+// original, what it is worth depends on how many shots the player has fired, so
+// a player who counts their shots can line up the 300 point hit
+
+// Row the UFO flies along, just below the boss health bar
+pub const UFO_ROW: i32 = 2;
+const SPRITE: &str = "<=O=>";
+// Ticks between each step sideways
+const MOVE_INTERVAL: u8 = 2;
+// Points for a hit, picked by the number of shots fired so far. The table goes
+// round every 15 shots, but the 300 slot only pays from the second time round, so
+// the 23rd shot and every 15th after it is worth the most
+const SCORES: [u32; 15] = [
+    100, 50, 50, 100, 150, 100, 100, 50, 300, 100, 100, 100, 50, 150, 100,
+];
+const FIRST_300_SHOT: u32 = 23;
+
+pub struct Ufo {
+    pub x: i32, // Left edge
+    direction: i32,
+    move_timer: u8,
+}
+
+impl Ufo {
+    // A UFO sliding in from just past one edge of the playfield
+    pub fn new(from_left: bool) -> Self {
+        let width = SPRITE.chars().count() as i32;
+        Ufo {
+            x: if from_left {
+                1 - width
+            } else {
+                WIDTH as i32 - 1
+            },
+            direction: if from_left { 1 } else { -1 },
+            move_timer: 0,
+        }
+    }
+
+    // Move along, `slow` times slower during Time Slow. Returns false once the UFO
+    // has left the playfield on the far side
+    pub fn tick(&mut self, slow: usize) -> bool {
+        self.move_timer += 1;
+        if self.move_timer >= MOVE_INTERVAL.saturating_mul(slow as u8) {
+            self.move_timer = 0;
+            self.x += self.direction;
+        }
+        self.on_screen()
+    }
+
+    // Whether any of the UFO can be seen
+    pub fn on_screen(&self) -> bool {
+        self.cells().any(|(x, y, _)| world::on_screen(x, y))
+    }
+
+    // Whether a bullet's path crosses the UFO
+    pub fn hit(&self, path: &[Cell]) -> bool {
+        path.iter()
+            .any(|&(x, y)| y == UFO_ROW && self.cells().any(|(ux, _, _)| ux == x))
+    }
+
+    pub fn center(&self) -> Cell {
+        (self.x + SPRITE.chars().count() as i32 / 2, UFO_ROW)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, char)> + '_ {
+        SPRITE
+            .chars()
+            .enumerate()
+            .map(|(i, c)| (self.x + i as i32, UFO_ROW, c))
+    }
+}
+
+// Points for hitting the UFO after firing `shots` shots
+pub fn score(shots: u32) -> u32 {
+    match SCORES[shots as usize % SCORES.len()] {
+        300 if shots < FIRST_300_SHOT => 100,
+        points => points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_23rd_shot_and_every_15th_after_scores_300() {
+        let jackpots: Vec<u32> = (0..70).filter(|&shots| score(shots) == 300).collect();
+        assert_eq!(jackpots, [23, 38, 53, 68]);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{HEIGHT, WIDTH};
+
+// The game world uses signed coordinates, so things can move past the edges of the
+// playfield without wrapping around or panicking. The playfield is the part of the
+// world from (0, 0) to (WIDTH - 1, HEIGHT - 1); anything outside it is clipped when
+// drawn and removed by the despawn rules below
+
+// Row the player's ship flies on
+pub const PLAYER_ROW: i32 = HEIGHT as i32 - 1;
+
+// Whether a world position is inside the playfield
+pub fn on_screen(x: i32, y: i32) -> bool {
+    (0..WIDTH as i32).contains(&x) && (0..HEIGHT as i32).contains(&y)
+}
+
+// The playfield cell a world position is drawn in, None when it is clipped
+pub fn to_screen(x: i32, y: i32) -> Option<(usize, usize)> {
+    if on_screen(x, y) {
+        Some((x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
+// Keep a column inside the playfield, for things that stop at the edges
+pub fn clamp_x(x: i32) -> i32 {
+    x.clamp(0, WIDTH as i32 - 1)
+}
+
+// Wrap a column around the playfield, for enemies that leave one side and come
+// back on the other
+pub fn wrap_x(x: i32) -> i32 {
+    x.rem_euclid(WIDTH as i32)
+}
+
+// Despawn rules. Bullets never come back once they leave the playfield, so they
+// are removed as soon as they are off-screen
+pub fn bullet_despawns(x: i32, y: i32) -> bool {
+    !on_screen(x, y)
+}
+
+// Powerups only fall, so they are removed once they drop below the bottom edge.
+// Enemies are never despawned here: they wrap at the sides and invade when they
+// reach PLAYER_ROW
+pub fn powerup_despawns(y: i32) -> bool {
+    y >= HEIGHT as i32
+}